@@ -4,20 +4,11 @@
 extern crate i3ipc;
 
 use i3ipc::I3EventListener;
-use i3ipc::Subscription;
+use std::io;
 
 fn main() {
     let mut listener = I3EventListener::connect().expect("failed to connect");
-    let subs = [
-        Subscription::Workspace,
-        Subscription::Output,
-        Subscription::Mode,
-        Subscription::Window,
-        Subscription::BarConfig,
-        Subscription::Binding,
-    ];
-    listener.subscribe(&subs).expect("failed to subscribe");
-    for event in listener.listen() {
-        println!("{:?}\n", event.expect("failed to get event"))
-    }
+    listener
+        .log_events(io::stdout())
+        .expect("failed to get event");
 }
@@ -2,46 +2,61 @@
 use reply;
 use serde_json as json;
 use std::collections::HashMap;
+use ParseError;
+
+pub fn build_command_outcome(val: &json::Value) -> Result<reply::CommandOutcome, ParseError> {
+    if !val.is_object() {
+        return Err(ParseError::InvalidField("outcome"));
+    }
+    let optional_str = |name| match val.get(name) {
+        Some(s) => s
+            .as_str()
+            .map(|s| Some(s.to_owned()))
+            .ok_or(ParseError::InvalidField(name)),
+        None => Ok(None),
+    };
+    Ok(reply::CommandOutcome {
+        success: bool_field(val, "success")?,
+        error: optional_str("error")?,
+        input: optional_str("input")?,
+        extra: extra_fields(val, &["success", "error", "input"]),
+    })
+}
 
 /// Recursively build the tree of containers from the given json value.
-pub fn build_tree(val: &json::Value) -> reply::Node {
-    reply::Node {
+pub fn build_tree(val: &json::Value) -> Result<reply::Node, ParseError> {
+    Ok(reply::Node {
         focus: match val.get("focus") {
-            Some(xs) => xs
-                .as_array()
-                .unwrap()
+            Some(xs) => as_array(xs, "focus")?
                 .iter()
-                .map(|x| x.as_i64().unwrap())
-                .collect(),
+                .map(|x| x.as_i64().ok_or(ParseError::InvalidField("focus")))
+                .collect::<Result<_, _>>()?,
             None => vec![],
         },
         nodes: match val.get("nodes") {
-            Some(nds) => nds
-                .as_array()
-                .unwrap()
+            Some(nds) => as_array(nds, "nodes")?
                 .iter()
-                .map(|n| build_tree(n))
-                .collect(),
+                .map(build_tree)
+                .collect::<Result<_, _>>()?,
             None => vec![],
         },
         floating_nodes: match val.get("floating_nodes") {
-            Some(nds) => nds
-                .as_array()
-                .unwrap()
+            Some(nds) => as_array(nds, "floating_nodes")?
                 .iter()
-                .map(|n| build_tree(n))
-                .collect(),
+                .map(build_tree)
+                .collect::<Result<_, _>>()?,
             None => vec![],
         },
-        id: val.get("id").unwrap().as_i64().unwrap(),
-        name: match val.get("name") {
-            Some(n) => match n.as_str() {
-                Some(s) => Some(s.to_owned()),
-                None => None,
-            },
-            None => None,
+        id: i64_field(val, "id")?,
+        name: val
+            .get("name")
+            .and_then(|n| n.as_str())
+            .map(|s| s.to_owned()),
+        num: match val.get("num") {
+            Some(&json::Value::Null) | None => None,
+            Some(num) => Some(num.as_i64().ok_or(ParseError::InvalidField("num"))? as i32),
         },
-        nodetype: match val.get("type").unwrap().as_str().unwrap() {
+        nodetype: match str_field(val, "type")? {
             "root" => reply::NodeType::Root,
             "output" => reply::NodeType::Output,
             "con" => reply::NodeType::Con,
@@ -49,21 +64,23 @@ pub fn build_tree(val: &json::Value) -> reply::Node {
             "workspace" => reply::NodeType::Workspace,
             "dockarea" => reply::NodeType::DockArea,
             other => {
+                reject_unknown("type", other)?;
                 warn!(target: "i3ipc", "Unknown NodeType {}", other);
                 reply::NodeType::Unknown
             }
         },
-        border: match val.get("border").unwrap().as_str().unwrap() {
+        border: match str_field(val, "border")? {
             "normal" => reply::NodeBorder::Normal,
             "none" => reply::NodeBorder::None,
             "pixel" => reply::NodeBorder::Pixel,
             other => {
+                reject_unknown("border", other)?;
                 warn!(target: "i3ipc", "Unknown NodeBorder {}", other);
                 reply::NodeBorder::Unknown
             }
         },
-        current_border_width: val.get("current_border_width").unwrap().as_i64().unwrap() as i32,
-        layout: match val.get("layout").unwrap().as_str().unwrap() {
+        current_border_width: i64_field(val, "current_border_width")? as i32,
+        layout: match str_field(val, "layout")? {
             "splith" => reply::NodeLayout::SplitH,
             "splitv" => reply::NodeLayout::SplitV,
             "stacked" => reply::NodeLayout::Stacked,
@@ -71,30 +88,210 @@ pub fn build_tree(val: &json::Value) -> reply::Node {
             "dockarea" => reply::NodeLayout::DockArea,
             "output" => reply::NodeLayout::Output,
             other => {
+                reject_unknown("layout", other)?;
                 warn!(target: "i3ipc", "Unknown NodeLayout {}", other);
                 reply::NodeLayout::Unknown
             }
         },
-        percent: match *val.get("percent").unwrap() {
-            json::Value::Number(ref f) => Some(f.as_f64().unwrap()),
-            json::Value::Null => None,
-            _ => unreachable!(),
-        },
-        rect: build_rect(val.get("rect").unwrap()),
-        window_rect: build_rect(val.get("window_rect").unwrap()),
-        deco_rect: build_rect(val.get("deco_rect").unwrap()),
-        geometry: build_rect(val.get("geometry").unwrap()),
-        window: match val.get("window").unwrap().clone() {
-            json::Value::Number(i) => Some(i.as_i64().unwrap() as i32),
+        percent: match val.get("percent") {
+            Some(json::Value::Number(f)) => f.as_f64(),
+            Some(&json::Value::Null) | None => None,
+            Some(_) => return Err(ParseError::InvalidField("percent")),
+        },
+        rect: rect_field(val, "rect")?,
+        window_rect: rect_field(val, "window_rect")?,
+        deco_rect: match val.get("deco_rect") {
+            Some(_) => Some(rect_field(val, "deco_rect")?),
+            None => None,
+        },
+        geometry: match val.get("geometry") {
+            Some(_) => Some(rect_field(val, "geometry")?),
+            None => None,
+        },
+        window: match *field(val, "window")? {
+            json::Value::Number(ref i) => match i.as_u64() {
+                Some(i) if i <= u64::from(u32::MAX) => Some(i as u32),
+                _ => return Err(ParseError::InvalidField("window")),
+            },
             json::Value::Null => None,
-            _ => unreachable!(),
+            _ => return Err(ParseError::InvalidField("window")),
+        },
+        #[cfg(feature = "sway-1-1")]
+        app_id: val
+            .get("app_id")
+            .and_then(|a| a.as_str())
+            .map(|s| s.to_owned()),
+        #[cfg(feature = "sway-1-1")]
+        representation: val
+            .get("representation")
+            .and_then(|r| r.as_str())
+            .map(|s| s.to_owned()),
+        window_properties: match val.get("window_properties") {
+            Some(props) if !props.is_object() => {
+                return Err(ParseError::InvalidField("window_properties"))
+            }
+            props => build_window_properties(props),
+        },
+        transient_for: match val
+            .get("window_properties")
+            .and_then(|p| p.get("transient_for"))
+        {
+            Some(json::Value::Number(t)) => match t.as_u64() {
+                Some(t) if t <= u64::from(u32::MAX) => Some(t as u32),
+                _ => return Err(ParseError::InvalidField("transient_for")),
+            },
+            _ => None,
         },
-        window_properties: build_window_properties(val.get("window_properties")),
-        urgent: val.get("urgent").unwrap().as_bool().unwrap(),
-        focused: val.get("focused").unwrap().as_bool().unwrap(),
+        urgent: bool_field(val, "urgent")?,
+        focused: bool_field(val, "focused")?,
+        marks: match val.get("marks") {
+            Some(marks) => as_array(marks, "marks")?
+                .iter()
+                .map(|m| {
+                    m.as_str()
+                        .map(|m| m.to_owned())
+                        .ok_or(ParseError::InvalidField("marks"))
+                })
+                .collect::<Result<_, _>>()?,
+            None => vec![],
+        },
+        swallows: match val.get("swallows") {
+            Some(swallows) => as_array(swallows, "swallows")?
+                .iter()
+                .map(build_swallow)
+                .collect::<Result<_, _>>()?,
+            None => vec![],
+        },
+        extra: node_extra(val),
+    })
+}
+
+/// The node fields that `build_tree` models.
+const NODE_FIELDS: &[&str] = &[
+    "focus",
+    "nodes",
+    "floating_nodes",
+    "id",
+    "name",
+    "num",
+    "type",
+    "border",
+    "current_border_width",
+    "layout",
+    "percent",
+    "rect",
+    "window_rect",
+    "deco_rect",
+    "geometry",
+    "window",
+    "window_properties",
+    "urgent",
+    "focused",
+    "marks",
+    "swallows",
+];
+
+/// The node fields that `build_tree` only models with the `sway-1-1` feature.
+#[cfg(feature = "sway-1-1")]
+const SWAY_NODE_FIELDS: &[&str] = &["app_id", "representation"];
+
+fn node_extra(val: &json::Value) -> HashMap<String, json::Value> {
+    #[allow(unused_mut)]
+    let mut extra = extra_fields(val, NODE_FIELDS);
+    #[cfg(feature = "sway-1-1")]
+    for name in SWAY_NODE_FIELDS {
+        extra.remove(*name);
+    }
+    extra
+}
+
+fn build_swallow(val: &json::Value) -> Result<reply::Swallow, ParseError> {
+    if !val.is_object() {
+        return Err(ParseError::InvalidField("swallows"));
+    }
+    let criterion = |name| val.get(name).and_then(|c| c.as_str()).map(|c| c.to_owned());
+    Ok(reply::Swallow {
+        class: criterion("class"),
+        instance: criterion("instance"),
+        title: criterion("title"),
+        window_role: criterion("window_role"),
+    })
+}
+
+/// Fails with `ParseError::UnknownEnumValue` if strict parsing is on, so the caller only falls
+/// back to an `Unknown` variant when it's off.
+pub fn reject_unknown(field: &'static str, value: &str) -> Result<(), ParseError> {
+    if ::strict_parsing() {
+        Err(ParseError::UnknownEnumValue {
+            field,
+            value: value.to_owned(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+fn field<'a>(val: &'a json::Value, name: &'static str) -> Result<&'a json::Value, ParseError> {
+    val.get(name).ok_or(ParseError::MissingField(name))
+}
+
+fn i64_field(val: &json::Value, name: &'static str) -> Result<i64, ParseError> {
+    field(val, name)?
+        .as_i64()
+        .ok_or(ParseError::InvalidField(name))
+}
+
+#[cfg(feature = "sway-1-1")]
+fn f64_field(val: &json::Value, name: &'static str) -> Result<f64, ParseError> {
+    field(val, name)?
+        .as_f64()
+        .ok_or(ParseError::InvalidField(name))
+}
+
+fn bool_field(val: &json::Value, name: &'static str) -> Result<bool, ParseError> {
+    field(val, name)?
+        .as_bool()
+        .ok_or(ParseError::InvalidField(name))
+}
+
+fn str_field<'a>(val: &'a json::Value, name: &'static str) -> Result<&'a str, ParseError> {
+    field(val, name)?
+        .as_str()
+        .ok_or(ParseError::InvalidField(name))
+}
+
+/// The fields of the object `val` whose names aren't in `known`, for the `extra` maps.
+fn extra_fields(val: &json::Value, known: &[&str]) -> HashMap<String, json::Value> {
+    match val.as_object() {
+        Some(obj) => obj
+            .iter()
+            .filter(|(k, _)| !known.contains(&k.as_str()))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+        None => HashMap::new(),
     }
 }
 
+fn as_array<'a>(
+    val: &'a json::Value,
+    name: &'static str,
+) -> Result<&'a Vec<json::Value>, ParseError> {
+    val.as_array().ok_or(ParseError::InvalidField(name))
+}
+
+/// The rect in the field `name` of `val`, as (x, y, width, height).
+fn rect_field(val: &json::Value, name: &'static str) -> Result<(i32, i32, i32, i32), ParseError> {
+    let jrect = field(val, name)?;
+    let get = |key| {
+        jrect
+            .get(key)
+            .and_then(|v| v.as_i64())
+            .map(|v| v as i32)
+            .ok_or(ParseError::InvalidField(name))
+    };
+    Ok((get("x")?, get("y")?, get("width")?, get("height")?))
+}
+
 pub fn build_window_properties(
     j: Option<&json::Value>,
 ) -> Option<HashMap<reply::WindowProperty, String>> {
@@ -116,10 +313,12 @@ pub fn build_window_properties(
                     }
                 };
                 if let Some(window_property) = window_property {
-                    map.insert(
-                        window_property,
-                        val.as_str().unwrap_or_default().to_string(),
-                    );
+                    let val = match *val {
+                        json::Value::String(ref s) => s.clone(),
+                        json::Value::Number(ref n) => n.to_string(),
+                        _ => String::new(),
+                    };
+                    map.insert(window_property, val);
                 }
             }
             Some(map)
@@ -127,31 +326,82 @@ pub fn build_window_properties(
     }
 }
 
-pub fn build_rect(jrect: &json::Value) -> (i32, i32, i32, i32) {
-    let x = jrect.get("x").unwrap().as_i64().unwrap() as i32;
-    let y = jrect.get("y").unwrap().as_i64().unwrap() as i32;
-    let width = jrect.get("width").unwrap().as_i64().unwrap() as i32;
-    let height = jrect.get("height").unwrap().as_i64().unwrap() as i32;
-    (x, y, width, height)
+pub fn build_workspace(w: &json::Value) -> Result<reply::Workspace, ParseError> {
+    Ok(reply::Workspace {
+        num: i64_field(w, "num")? as i32,
+        name: str_field(w, "name")?.to_owned(),
+        visible: bool_field(w, "visible")?,
+        focused: bool_field(w, "focused")?,
+        urgent: bool_field(w, "urgent")?,
+        rect: rect_field(w, "rect")?,
+        output: str_field(w, "output")?.to_owned(),
+    })
 }
 
-pub fn build_bar_config(j: &json::Value) -> reply::BarConfig {
-    reply::BarConfig {
-        id: j.get("id").unwrap().as_str().unwrap().to_owned(),
-        mode: j.get("mode").unwrap().as_str().unwrap().to_owned(),
-        position: j.get("position").unwrap().as_str().unwrap().to_owned(),
-        status_command: j
-            .get("status_command")
-            .unwrap()
+pub fn build_output(o: &json::Value) -> Result<reply::Output, ParseError> {
+    #[cfg(feature = "sway-1-1")]
+    let optional_str = |name| match o.get(name) {
+        Some(s) => s
             .as_str()
-            .unwrap()
-            .to_owned(),
-        font: j.get("font").unwrap().as_str().unwrap().to_owned(),
-        workspace_buttons: j.get("workspace_buttons").unwrap().as_bool().unwrap(),
-        binding_mode_indicator: j.get("binding_mode_indicator").unwrap().as_bool().unwrap(),
-        verbose: j.get("verbose").unwrap().as_bool().unwrap(),
+            .map(|s| Some(s.to_owned()))
+            .ok_or(ParseError::InvalidField(name)),
+        None => Ok(None),
+    };
+    Ok(reply::Output {
+        name: str_field(o, "name")?.to_owned(),
+        #[cfg(feature = "sway-1-1")]
+        make: str_field(o, "make")?.to_owned(),
+        #[cfg(feature = "sway-1-1")]
+        model: str_field(o, "model")?.to_owned(),
+        #[cfg(feature = "sway-1-1")]
+        serial: str_field(o, "serial")?.to_owned(),
+        #[cfg(feature = "sway-1-1")]
+        scale: match o.get("scale") {
+            Some(_) => Some(f64_field(o, "scale")?),
+            None => None,
+        },
+        #[cfg(feature = "sway-1-1")]
+        subpixel_hinting: optional_str("subpixel_hinting")?,
+        #[cfg(feature = "sway-1-1")]
+        transform: optional_str("transform")?,
+        #[cfg(feature = "sway-1-1")]
+        modes: build_modes(field(o, "modes")?)?,
+        #[cfg(feature = "sway-1-1")]
+        current_mode: match o.get("current_mode") {
+            Some(mode) => Some(build_mode(mode)?),
+            None => None,
+        },
+        active: bool_field(o, "active")?,
+        primary: bool_field(o, "primary")?,
+        current_workspace: match *field(o, "current_workspace")? {
+            json::Value::String(ref c_w) => Some(c_w.clone()),
+            json::Value::Null => None,
+            _ => return Err(ParseError::InvalidField("current_workspace")),
+        },
+        #[cfg(feature = "sway-1-1")]
+        dpms: bool_field(o, "dpms")?,
+        rect: rect_field(o, "rect")?,
+    })
+}
+
+pub fn build_bar_config(j: &json::Value) -> Result<reply::BarConfig, ParseError> {
+    Ok(reply::BarConfig {
+        id: str_field(j, "id")?.to_owned(),
+        mode: str_field(j, "mode")?.to_owned(),
+        position: str_field(j, "position")?.to_owned(),
+        status_command: str_field(j, "status_command")?.to_owned(),
+        font: str_field(j, "font")?.to_owned(),
+        workspace_buttons: bool_field(j, "workspace_buttons")?,
+        binding_mode_indicator: bool_field(j, "binding_mode_indicator")?,
+        verbose: bool_field(j, "verbose")?,
+        modifier: match j.get("modifier").and_then(|m| m.as_str()) {
+            Some(modifier) => build_modifiers(modifier.split('+')),
+            None => vec![],
+        },
         colors: {
-            let colors = j.get("colors").unwrap().as_object().unwrap();
+            let colors = field(j, "colors")?
+                .as_object()
+                .ok_or(ParseError::InvalidField("colors"))?;
             let mut map = HashMap::new();
             for c in colors.keys() {
                 let enum_key = match c.as_ref() {
@@ -188,31 +438,135 @@ pub fn build_bar_config(j: &json::Value) -> reply::BarConfig {
                         reply::ColorableBarPart::Unknown
                     }
                 };
-                let hex = colors.get(c).unwrap().as_str().unwrap().to_owned();
+                let hex = colors[c]
+                    .as_str()
+                    .ok_or(ParseError::InvalidField("colors"))?
+                    .to_owned();
                 map.insert(enum_key, hex);
             }
             map
         },
-    }
+        extra: extra_fields(j, BAR_CONFIG_FIELDS),
+    })
+}
+
+/// The bar config fields that `build_bar_config` models.
+const BAR_CONFIG_FIELDS: &[&str] = &[
+    "id",
+    "mode",
+    "position",
+    "status_command",
+    "font",
+    "workspace_buttons",
+    "binding_mode_indicator",
+    "verbose",
+    "modifier",
+    "colors",
+];
+
+#[cfg(feature = "i3-4-14")]
+pub fn build_config(j: &json::Value) -> Result<reply::Config, ParseError> {
+    Ok(reply::Config {
+        config: str_field(j, "config")?.to_owned(),
+        #[cfg(feature = "i3-4-20")]
+        included_configs: match j.get("included_configs") {
+            Some(included) => as_array(included, "included_configs")?
+                .iter()
+                .map(build_included_config)
+                .collect::<Result<_, _>>()?,
+            None => vec![],
+        },
+    })
+}
+
+#[cfg(feature = "i3-4-20")]
+fn build_included_config(c: &json::Value) -> Result<reply::IncludedConfig, ParseError> {
+    let contents = |name| {
+        c.get(name)
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_owned())
+            .ok_or(ParseError::InvalidField("included_configs"))
+    };
+    Ok(reply::IncludedConfig {
+        path: contents("path")?,
+        raw_contents: contents("raw_contents")?,
+        variable_replaced_contents: contents("variable_replaced_contents")?,
+    })
+}
+
+/// Parses modifier key names, skipping (and warning about) any we don't know.
+pub fn build_modifiers<'a, I: IntoIterator<Item = &'a str>>(names: I) -> Vec<reply::ModKey> {
+    names
+        .into_iter()
+        .filter_map(|name| match name.to_lowercase().as_ref() {
+            "shift" => Some(reply::ModKey::Shift),
+            "ctrl" | "control" => Some(reply::ModKey::Control),
+            "mod1" => Some(reply::ModKey::Mod1),
+            "mod2" => Some(reply::ModKey::Mod2),
+            "mod3" => Some(reply::ModKey::Mod3),
+            "mod4" => Some(reply::ModKey::Mod4),
+            "mod5" => Some(reply::ModKey::Mod5),
+            "super" => Some(reply::ModKey::Super),
+            _ => {
+                warn!(target: "i3ipc", "Unknown ModKey {}", name);
+                None
+            }
+        })
+        .collect()
 }
 
 #[cfg(feature = "sway-1-1")]
-pub fn build_modes(j: &json::Value) -> Vec<reply::Mode> {
-    let mut res: Vec<reply::Mode>= Vec::new();
-    for mode in j.as_array().unwrap() {
-        res.push(build_mode(mode))
+pub fn build_transform(transform: &str) -> reply::Transform {
+    match transform.trim_start_matches("rotate_") {
+        "normal" => reply::Transform::Normal,
+        "90" => reply::Transform::Rotate90,
+        "180" => reply::Transform::Rotate180,
+        "270" => reply::Transform::Rotate270,
+        "flipped" => reply::Transform::Flipped,
+        "flipped-90" | "flipped_90" => reply::Transform::Flipped90,
+        "flipped-180" | "flipped_180" => reply::Transform::Flipped180,
+        "flipped-270" | "flipped_270" => reply::Transform::Flipped270,
+        _ => {
+            warn!(target: "i3ipc", "Unknown Transform {}", transform);
+            reply::Transform::Unknown
+        }
     }
-    res
 }
 
 #[cfg(feature = "sway-1-1")]
-pub fn build_mode(jmode: &json::Value) -> reply::Mode {
-    let width = jmode.get("width").unwrap().as_i64().unwrap() as i32;
-    let height = jmode.get("height").unwrap().as_i64().unwrap() as i32;
-    let refresh = jmode.get("refresh").unwrap().as_i64().unwrap() as i32;
-    reply::Mode {
-        width: width,
-        height: height,
-        refresh: refresh
+pub fn build_subpixel(subpixel: &str) -> reply::Subpixel {
+    match subpixel {
+        "rgb" => reply::Subpixel::Rgb,
+        "bgr" => reply::Subpixel::Bgr,
+        "vrgb" => reply::Subpixel::Vrgb,
+        "vbgr" => reply::Subpixel::Vbgr,
+        "none" => reply::Subpixel::None,
+        "unknown" => reply::Subpixel::Unknown,
+        other => {
+            warn!(target: "i3ipc", "Unknown Subpixel {}", other);
+            reply::Subpixel::Unknown
+        }
     }
 }
+
+#[cfg(feature = "sway-1-1")]
+pub fn build_modes(j: &json::Value) -> Result<Vec<reply::Mode>, ParseError> {
+    as_array(j, "modes")?.iter().map(build_mode).collect()
+}
+
+#[cfg(feature = "sway-1-1")]
+pub fn build_mode(jmode: &json::Value) -> Result<reply::Mode, ParseError> {
+    // sway sends integers, but don't fail if any of these ever arrive as floats.
+    let refresh = f64_field(jmode, "refresh")?;
+    // a refresh rate below 1 Hz makes no sense, so such a value must already be in Hz
+    let refresh = if refresh < 1000.0 {
+        refresh * 1000.0
+    } else {
+        refresh
+    };
+    Ok(reply::Mode {
+        width: f64_field(jmode, "width")?.round() as i32,
+        height: f64_field(jmode, "height")?.round() as i32,
+        refresh: refresh.round() as i32,
+    })
+}
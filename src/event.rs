@@ -2,13 +2,18 @@
 
 use common;
 use reply;
+use serde::de::{Deserialize, Deserializer, Error, IgnoredAny, MapAccess, Visitor};
 use serde_json as json;
+use std::collections::HashMap;
+use std::fmt;
 use std::str::FromStr;
+use ParseError;
 
 use event::inner::*;
 
 /// An event passed back from i3.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum Event {
     WorkspaceEvent(WorkspaceEventInfo),
     OutputEvent(OutputEventInfo),
@@ -22,25 +27,103 @@ pub enum Event {
     ShutdownEvent(ShutdownEventInfo),
 }
 
-/// Data for `WorkspaceEvent`.
+/// A one-line summary of the event, such as `window focus: 94557345 "Firefox"`, for logs and
+/// quick diagnostics. Use the `Debug` form to see every field.
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Event::WorkspaceEvent(ref e) => {
+                write!(f, "workspace {}", e.raw_change)?;
+                match e.current.as_ref().and_then(|c| c.name.as_ref()) {
+                    Some(name) => write!(f, ": {:?}", name),
+                    None => Ok(()),
+                }
+            }
+            Event::OutputEvent(ref e) => write!(f, "output {}", e.raw_change),
+            Event::ModeEvent(ref e) => write!(f, "mode {}", e.change),
+            Event::WindowEvent(ref e) => {
+                write!(f, "window {}: {}", e.raw_change, e.container.id)?;
+                match e.container.title() {
+                    Some(title) => write!(f, " {:?}", title),
+                    None => Ok(()),
+                }
+            }
+            Event::BarConfigEvent(ref e) => write!(f, "barconfig_update: {}", e.bar_config.id),
+            Event::BindingEvent(ref e) => {
+                write!(f, "binding {}: {}", e.raw_change, e.binding.command)
+            }
+            #[cfg(feature = "i3-4-14")]
+            Event::ShutdownEvent(ref e) => write!(f, "shutdown {}", e.raw_change),
+        }
+    }
+}
+
+/// An error parsing an event. Unlike the `json::Error` the `FromStr` impls return, this keeps
+/// a `ParseError` intact, so `build_event` can report unknown enum values as such.
 #[derive(Debug)]
+pub(crate) enum EventParseError {
+    Json(json::Error),
+    Parse(ParseError),
+}
+
+impl From<json::Error> for EventParseError {
+    fn from(error: json::Error) -> EventParseError {
+        EventParseError::Json(error)
+    }
+}
+
+impl From<ParseError> for EventParseError {
+    fn from(error: ParseError) -> EventParseError {
+        EventParseError::Parse(error)
+    }
+}
+
+impl From<EventParseError> for json::Error {
+    fn from(error: EventParseError) -> json::Error {
+        match error {
+            EventParseError::Json(error) => error,
+            EventParseError::Parse(error) => json::Error::custom(error),
+        }
+    }
+}
+
+/// Data for `WorkspaceEvent`.
+#[derive(Debug, Clone)]
 pub struct WorkspaceEventInfo {
     /// The type of change.
     pub change: WorkspaceChange,
     /// Will be `Some` if the type of event affects the workspace.
     pub current: Option<reply::Node>,
-    /// Will be `Some` only when `change == Focus` *and* there was a previous workspace.
+    /// Will be `Some` whenever i3 sends a previous workspace. i3 documents this for
+    /// `change == Focus` *and* there was a previous workspace, but it is parsed for any change
+    /// (such as `Rename`) that carries it.
     /// Note that if the previous workspace was empty it will get destroyed when switching, but
     /// will still appear here.
     pub old: Option<reply::Node>,
+    /// The change exactly as i3 sent it, so nothing is lost when `change` is `Unknown`.
+    pub raw_change: String,
 }
 
-impl FromStr for WorkspaceEventInfo {
-    type Err = json::error::Error;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+impl WorkspaceEventInfo {
+    /// Whether this event is about a workspace being renamed.
+    pub fn is_rename(&self) -> bool {
+        self.change == WorkspaceChange::Rename
+    }
+
+    /// Whether the `current` workspace is a named one, that is its `num` is -1. None if there
+    /// is no `current` workspace or i3 didn't send its number.
+    pub fn current_is_named(&self) -> Option<bool> {
+        self.current
+            .as_ref()
+            .and_then(|c| c.num)
+            .map(|num| num == -1)
+    }
+
+    pub(crate) fn parse(s: &str) -> Result<Self, EventParseError> {
         let val: json::Value = json::from_str(s)?;
+        let raw_change = val.get("change").unwrap().as_str().unwrap();
         Ok(WorkspaceEventInfo {
-            change: match val.get("change").unwrap().as_str().unwrap() {
+            change: match raw_change {
                 "focus" => WorkspaceChange::Focus,
                 "init" => WorkspaceChange::Init,
                 "empty" => WorkspaceChange::Empty,
@@ -50,50 +133,223 @@ impl FromStr for WorkspaceEventInfo {
                 "move" => WorkspaceChange::Move,
                 "restored" => WorkspaceChange::Restored,
                 other => {
+                    common::reject_unknown("change", other)?;
                     warn!(target: "i3ipc", "Unknown WorkspaceChange {}", other);
                     WorkspaceChange::Unknown
                 }
             },
             current: match val.get("current").unwrap().clone() {
                 json::Value::Null => None,
-                val => Some(common::build_tree(&val)),
+                val => Some(common::build_tree(&val)?),
             },
             old: match val.get("old") {
                 Some(o) => match o.clone() {
                     json::Value::Null => None,
-                    val => Some(common::build_tree(&val)),
+                    val => Some(common::build_tree(&val)?),
                 },
                 None => None,
             },
+            raw_change: raw_change.to_owned(),
         })
     }
 }
 
-/// Data for `OutputEvent`.
+impl FromStr for WorkspaceEventInfo {
+    type Err = json::error::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(WorkspaceEventInfo::parse(s)?)
+    }
+}
+
+/// A live list of workspaces, kept up to date by applying workspace events to a snapshot from
+/// `get_workspaces`.
+///
+/// Workspace events only describe the workspace container, so some fields are a best guess:
+/// new workspaces are assumed to be on the output of the focused workspace, and a workspace
+/// that moved takes the output of another workspace with the same rect. Take a fresh snapshot
+/// after `Reload` or `Restored` changes, which aren't applied.
 #[derive(Debug)]
+pub struct WorkspaceState {
+    workspaces: Vec<reply::Workspace>,
+    /// Container ids of workspaces seen in events, mapped to their names. Needed to find a
+    /// workspace again after it's been renamed.
+    names: HashMap<i64, String>,
+}
+
+impl WorkspaceState {
+    /// Starts from the workspaces as returned by `get_workspaces`.
+    pub fn from_snapshot(snapshot: reply::Workspaces) -> WorkspaceState {
+        WorkspaceState {
+            workspaces: snapshot.workspaces,
+            names: HashMap::new(),
+        }
+    }
+
+    /// The current list of workspaces.
+    pub fn workspaces(&self) -> &[reply::Workspace] {
+        &self.workspaces
+    }
+
+    /// Updates the list with a workspace event.
+    pub fn apply(&mut self, event: &WorkspaceEventInfo) {
+        let current = match event.current {
+            Some(ref current) => current,
+            None => return,
+        };
+        let name = match current.name {
+            Some(ref name) => name.clone(),
+            None => return,
+        };
+        let index = self
+            .names
+            .get(&current.id)
+            .and_then(|known| self.position(known))
+            .or_else(|| self.position(&name))
+            .or_else(|| match event.change {
+                WorkspaceChange::Rename => self.renamed_from_snapshot(event, current),
+                _ => None,
+            });
+        self.names.insert(current.id, name.clone());
+
+        match (&event.change, index) {
+            (&WorkspaceChange::Init, None) => {
+                let output = self
+                    .workspaces
+                    .iter()
+                    .find(|w| w.focused)
+                    .map(|w| w.output.clone())
+                    .unwrap_or_default();
+                self.workspaces.push(reply::Workspace {
+                    num: workspace_num(&name),
+                    name,
+                    visible: false,
+                    focused: false,
+                    urgent: current.urgent,
+                    rect: current.rect,
+                    output,
+                });
+            }
+            (&WorkspaceChange::Empty, Some(i)) => {
+                self.workspaces.remove(i);
+                self.names.remove(&current.id);
+            }
+            (&WorkspaceChange::Focus, Some(i)) => {
+                let output = self.workspaces[i].output.clone();
+                for workspace in &mut self.workspaces {
+                    workspace.focused = false;
+                    if workspace.output == output {
+                        workspace.visible = false;
+                    }
+                }
+                let workspace = &mut self.workspaces[i];
+                workspace.focused = true;
+                workspace.visible = true;
+                workspace.urgent = current.urgent;
+            }
+            (&WorkspaceChange::Rename, Some(i)) => {
+                let workspace = &mut self.workspaces[i];
+                workspace.num = workspace_num(&name);
+                workspace.name = name;
+            }
+            (&WorkspaceChange::Move, Some(i)) => {
+                let output = self
+                    .workspaces
+                    .iter()
+                    .enumerate()
+                    .find(|&(j, w)| j != i && w.rect == current.rect)
+                    .map(|(_, w)| w.output.clone());
+                let workspace = &mut self.workspaces[i];
+                workspace.rect = current.rect;
+                if let Some(output) = output {
+                    workspace.output = output;
+                }
+            }
+            (&WorkspaceChange::Urgent, Some(i)) => {
+                self.workspaces[i].urgent = current.urgent;
+            }
+            _ => {}
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.workspaces.iter().position(|w| w.name == name)
+    }
+
+    /// Finds the workspace a rename event is about when it hasn't been seen in an event before,
+    /// so only its old name from the snapshot is known. Uses `old` if it describes the same
+    /// container; i3 usually leaves it out, in which case this falls back to the workspaces with
+    /// the same rect that no event has named yet, preferring the focused one if there are
+    /// several.
+    fn renamed_from_snapshot(
+        &self,
+        event: &WorkspaceEventInfo,
+        current: &reply::Node,
+    ) -> Option<usize> {
+        let old_name = event
+            .old
+            .as_ref()
+            .filter(|old| old.id == current.id)
+            .and_then(|old| old.name.as_ref());
+        if let Some(old_name) = old_name {
+            return self.position(old_name);
+        }
+        let candidates: Vec<usize> = self
+            .workspaces
+            .iter()
+            .enumerate()
+            .filter(|&(_, w)| w.rect == current.rect && !self.names.values().any(|n| *n == w.name))
+            .map(|(i, _)| i)
+            .collect();
+        match candidates.len() {
+            1 => Some(candidates[0]),
+            _ => candidates.into_iter().find(|&i| self.workspaces[i].focused),
+        }
+    }
+}
+
+/// The number i3 gives a workspace with the given name: the number the name starts with, or -1.
+fn workspace_num(name: &str) -> i32 {
+    let digits: String = name.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().unwrap_or(-1)
+}
+
+/// Data for `OutputEvent`.
+#[derive(Debug, Clone)]
 pub struct OutputEventInfo {
     /// The type of change.
     pub change: OutputChange,
+    /// The change exactly as i3 sent it. Both i3 and Sway currently only document
+    /// "unspecified", but this keeps anything else they send from being lost.
+    pub raw_change: String,
 }
 
 impl FromStr for OutputEventInfo {
     type Err = json::error::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(OutputEventInfo::parse(s)?)
+    }
+}
+
+impl OutputEventInfo {
+    pub(crate) fn parse(s: &str) -> Result<Self, EventParseError> {
         let val: json::Value = json::from_str(s)?;
+        let raw_change = val.get("change").unwrap().as_str().unwrap();
         Ok(OutputEventInfo {
-            change: match val.get("change").unwrap().as_str().unwrap() {
+            change: match raw_change {
                 "unspecified" => OutputChange::Unspecified,
                 other => {
+                    common::reject_unknown("change", other)?;
                     warn!(target: "i3ipc", "Unknown OutputChange {}", other);
                     OutputChange::Unknown
                 }
             },
+            raw_change: raw_change.to_owned(),
         })
     }
 }
 
 /// Data for `ModeEvent`.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ModeEventInfo {
     /// The name of current mode in use. It is the same as specified in config when creating a
     /// mode. The default mode is simply named default.
@@ -103,6 +359,12 @@ pub struct ModeEventInfo {
 impl FromStr for ModeEventInfo {
     type Err = json::error::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(ModeEventInfo::parse(s)?)
+    }
+}
+
+impl ModeEventInfo {
+    pub(crate) fn parse(s: &str) -> Result<Self, EventParseError> {
         let val: json::Value = json::from_str(s)?;
         Ok(ModeEventInfo {
             change: val.get("change").unwrap().as_str().unwrap().to_owned(),
@@ -111,7 +373,7 @@ impl FromStr for ModeEventInfo {
 }
 
 /// Data for `WindowEvent`.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WindowEventInfo {
     /// Indicates the type of change
     pub change: WindowChange,
@@ -119,14 +381,30 @@ pub struct WindowEventInfo {
     /// the initial name of the newly reparented window (e.g. if you run urxvt with a shell that
     /// changes the title, you will still at this point get the window title as "urxvt").
     pub container: reply::Node,
+    /// The change exactly as i3 sent it. Useful when `change` is `Unknown`, for example for
+    /// "mark" events received without the `i3-4-13` feature enabled.
+    pub raw_change: String,
 }
 
-impl FromStr for WindowEventInfo {
-    type Err = json::error::Error;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let val: json::Value = json::from_str(s)?;
+impl WindowEventInfo {
+    /// For an `Urgent` change, whether the window became urgent (true) or stopped being urgent
+    /// (false). None for other changes.
+    pub fn became_urgent(&self) -> Option<bool> {
+        if self.change == WindowChange::Urgent {
+            Some(self.container.urgent)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Result<Self, EventParseError> {
+        let raw: RawWindowEvent = json::from_str(s)?;
+        Ok(WindowEventInfo::build(raw.change, &raw.container)?)
+    }
+
+    fn build(raw_change: String, container: &json::Value) -> Result<Self, ParseError> {
         Ok(WindowEventInfo {
-            change: match val.get("change").unwrap().as_str().unwrap() {
+            change: match raw_change.as_str() {
                 "new" => WindowChange::New,
                 "close" => WindowChange::Close,
                 "focus" => WindowChange::Focus,
@@ -140,17 +418,76 @@ impl FromStr for WindowEventInfo {
                 "mark" => WindowChange::Mark,
 
                 other => {
+                    common::reject_unknown("change", other)?;
                     warn!(target: "i3ipc", "Unknown WindowChange {}", other);
                     WindowChange::Unknown
                 }
             },
-            container: common::build_tree(val.get("container").unwrap()),
+            container: common::build_tree(container)?,
+            raw_change,
         })
     }
 }
 
+/// The fields of a window event, before they are turned into a `WindowEventInfo`.
+struct RawWindowEvent {
+    change: String,
+    container: json::Value,
+}
+
+/// Window events are the most frequent ones, so they are deserialized straight from the payload
+/// instead of going through an intermediate `json::Value` for the whole message. Only the
+/// container is still collected into a `json::Value`, since that's what `build_tree` works on.
+impl<'de> Deserialize<'de> for RawWindowEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct WindowEventVisitor;
+
+        impl<'de> Visitor<'de> for WindowEventVisitor {
+            type Value = RawWindowEvent;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a window event")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RawWindowEvent, A::Error> {
+                let mut change: Option<String> = None;
+                let mut container: Option<json::Value> = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "change" => change = Some(map.next_value()?),
+                        "container" => container = Some(map.next_value()?),
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(RawWindowEvent {
+                    change: change.ok_or_else(|| A::Error::missing_field("change"))?,
+                    container: container.ok_or_else(|| A::Error::missing_field("container"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_map(WindowEventVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for WindowEventInfo {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawWindowEvent::deserialize(deserializer)?;
+        WindowEventInfo::build(raw.change, &raw.container).map_err(D::Error::custom)
+    }
+}
+
+impl FromStr for WindowEventInfo {
+    type Err = json::error::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(WindowEventInfo::parse(s)?)
+    }
+}
+
 /// Data for `BarConfigEvent`.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BarConfigEventInfo {
     /// The new i3 bar configuration.
     pub bar_config: reply::BarConfig,
@@ -159,9 +496,15 @@ pub struct BarConfigEventInfo {
 impl FromStr for BarConfigEventInfo {
     type Err = json::error::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(BarConfigEventInfo::parse(s)?)
+    }
+}
+
+impl BarConfigEventInfo {
+    pub(crate) fn parse(s: &str) -> Result<Self, EventParseError> {
         let val: json::Value = json::from_str(s)?;
         Ok(BarConfigEventInfo {
-            bar_config: common::build_bar_config(&val),
+            bar_config: common::build_bar_config(&val)?,
         })
     }
 }
@@ -169,23 +512,45 @@ impl FromStr for BarConfigEventInfo {
 /// Data for `BindingEvent`.
 ///
 /// Reports on the details of a binding that ran a command because of user input.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BindingEventInfo {
     /// Indicates what sort of binding event was triggered (right now it will always be "run" but
     /// that may be expanded in the future).
     pub change: BindingChange,
     pub binding: Binding,
+    /// The change exactly as i3 sent it, so nothing is lost when `change` is `Unknown`.
+    pub raw_change: String,
 }
 
 impl FromStr for BindingEventInfo {
     type Err = json::error::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(BindingEventInfo::parse(s)?)
+    }
+}
+
+impl BindingEventInfo {
+    pub(crate) fn parse(s: &str) -> Result<Self, EventParseError> {
         let val: json::Value = json::from_str(s)?;
         let bind = val.get("binding").unwrap();
+        let raw_change = val.get("change").unwrap().as_str().unwrap();
+        let symbols: Vec<String> = match bind.get("symbols").and_then(|s| s.as_array()) {
+            Some(symbols) => symbols
+                .iter()
+                .filter_map(|s| s.as_str())
+                .map(|s| s.to_owned())
+                .collect(),
+            None => bind
+                .get("symbol")
+                .and_then(|s| s.as_str())
+                .map(|s| vec![s.to_owned()])
+                .unwrap_or_default(),
+        };
         Ok(BindingEventInfo {
-            change: match val.get("change").unwrap().as_str().unwrap() {
+            change: match raw_change {
                 "run" => BindingChange::Run,
                 other => {
+                    common::reject_unknown("change", other)?;
                     warn!(target: "i3ipc", "Unknown BindingChange {}", other);
                     BindingChange::Unknown
                 }
@@ -201,30 +566,39 @@ impl FromStr for BindingEventInfo {
                     .map(|m| m.as_str().unwrap().to_owned())
                     .collect(),
                 input_code: bind.get("input_code").unwrap().as_i64().unwrap() as i32,
-                symbol: match bind.get("symbol").unwrap().clone() {
-                    json::Value::String(s) => Some(s),
-                    json::Value::Null => None,
+                symbol: match bind.get("symbol") {
+                    Some(json::Value::String(s)) => Some(s.clone()),
+                    Some(json::Value::Null) | None => symbols.first().cloned(),
                     _ => unreachable!(),
                 },
+                symbols,
+                input_device: bind
+                    .get("input_device")
+                    .and_then(|d| d.as_str())
+                    .map(|d| d.to_owned()),
                 input_type: match bind.get("input_type").unwrap().as_str().unwrap() {
                     "keyboard" => InputType::Keyboard,
                     "mouse" => InputType::Mouse,
                     other => {
+                        common::reject_unknown("input_type", other)?;
                         warn!(target: "i3ipc", "Unknown InputType {}", other);
                         InputType::Unknown
                     }
                 },
             },
+            raw_change: raw_change.to_owned(),
         })
     }
 }
 
 /// Data for `ShutdownEvent`.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg(feature = "i3-4-14")]
 #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-14")))]
 pub struct ShutdownEventInfo {
     pub change: ShutdownChange,
+    /// The change exactly as i3 sent it, so nothing is lost when `change` is `Unknown`.
+    pub raw_change: String,
 }
 
 #[cfg(feature = "i3-4-14")]
@@ -232,23 +606,35 @@ pub struct ShutdownEventInfo {
 impl FromStr for ShutdownEventInfo {
     type Err = json::error::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(ShutdownEventInfo::parse(s)?)
+    }
+}
+
+#[cfg(feature = "i3-4-14")]
+impl ShutdownEventInfo {
+    pub(crate) fn parse(s: &str) -> Result<Self, EventParseError> {
         let val: json::Value = json::from_str(s)?;
-        let change = match val.get("change").unwrap().as_str().unwrap() {
+        let raw_change = val.get("change").unwrap().as_str().unwrap();
+        let change = match raw_change {
             "restart" => ShutdownChange::Restart,
             "exit" => ShutdownChange::Exit,
             other => {
+                common::reject_unknown("change", other)?;
                 warn!(target: "i3ipc", "Unknown ShutdownChange {}", other);
                 ShutdownChange::Unknown
             }
         };
-        Ok(ShutdownEventInfo { change })
+        Ok(ShutdownEventInfo {
+            change,
+            raw_change: raw_change.to_owned(),
+        })
     }
 }
 
 /// Less important types
 pub mod inner {
     /// The kind of workspace change.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
     pub enum WorkspaceChange {
         Focus,
         Init,
@@ -263,7 +649,7 @@ pub mod inner {
     }
 
     /// The kind of output change.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
     pub enum OutputChange {
         Unspecified,
         /// An OutputChange we don't support yet.
@@ -271,7 +657,7 @@ pub mod inner {
     }
 
     /// The kind of window change.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
     pub enum WindowChange {
         /// The window has become managed by i3.
         New,
@@ -291,6 +677,9 @@ pub mod inner {
         Urgent,
 
         /// A mark has been added to or removed from the window.
+        ///
+        /// i3 sends these from version 4.13 on. Without the `i3-4-13` feature they are
+        /// reported as `Unknown`, with "mark" in `WindowEventInfo::raw_change`.
         #[cfg(feature = "i3-4-13")]
         #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-13")))]
         Mark,
@@ -300,7 +689,7 @@ pub mod inner {
     }
 
     /// Either keyboard or mouse.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
     pub enum InputType {
         Keyboard,
         Mouse,
@@ -309,7 +698,7 @@ pub mod inner {
     }
 
     /// Contains details about the binding that was run.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
     pub struct Binding {
         /// The i3 command that is configured to run for this binding.
         pub command: String,
@@ -326,12 +715,27 @@ pub mod inner {
         /// given symbol. Otherwise it will be None.
         pub symbol: Option<String>,
 
+        /// Every symbol the binding was configured with. Newer versions may send several in a
+        /// `symbols` array; otherwise this holds `symbol`, if any.
+        pub symbols: Vec<String>,
+
+        /// The input device that triggered the binding, if i3 or Sway said.
+        pub input_device: Option<String>,
+
         /// Will be Keyboard or Mouse depending on whether this was a keyboard or mouse binding.
         pub input_type: InputType,
     }
 
+    impl Binding {
+        /// The modifier keys in `event_state_mask`. Names that aren't modifiers (such as
+        /// keyboard groups) are skipped.
+        pub fn modifiers(&self) -> Vec<::reply::ModKey> {
+            ::common::build_modifiers(self.event_state_mask.iter().map(|s| s.as_ref()))
+        }
+    }
+
     /// The kind of binding change.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
     pub enum BindingChange {
         Run,
         /// A BindingChange we don't support yet.
@@ -339,7 +743,7 @@ pub mod inner {
     }
 
     /// The kind of shutdown change.
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, Clone, PartialEq)]
     #[cfg(feature = "i3-4-14")]
     #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-14")))]
     pub enum ShutdownChange {
@@ -18,16 +18,31 @@
 #![cfg_attr(feature = "dox", feature(doc_cfg))]
 
 extern crate byteorder;
+#[cfg(feature = "logging")]
 #[macro_use]
 extern crate log;
 extern crate serde;
 extern crate serde_json;
 
+/// Without the `logging` feature warnings are dropped, but their arguments are still
+/// type-checked.
+#[cfg(not(feature = "logging"))]
+macro_rules! warn {
+    (target: $target:expr, $($arg:tt)+) => {{
+        let _ = $target;
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+use std::cell::{Cell, RefCell};
 use std::error::Error;
 use std::io::prelude::*;
+use std::net::Shutdown;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
 use std::os::unix::net::UnixStream;
-use std::str::FromStr;
-use std::{env, fmt, io, process};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use std::{env, fmt, io, process, str, thread};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use serde_json as json;
@@ -35,17 +50,22 @@ use serde_json as json;
 mod common;
 pub mod event;
 pub mod reply;
+pub mod statusline;
 
 /// An error initializing a connection.
 ///
 /// It first involves first getting the i3 socket path, then connecting to the socket. Either part
-/// could go wrong which is why there are two possibilities here.
+/// could go wrong which is why there are two possibilities here, plus a third for connecting
+/// with a time limit.
 #[derive(Debug)]
 pub enum EstablishError {
     /// An error while getting the socket path
     GetSocketPathError(io::Error),
     /// An error while accessing the socket
     SocketError(io::Error),
+    /// Finding and connecting to the socket took longer than the timeout given to
+    /// `I3Connection::connect_timeout`.
+    Timeout,
 }
 
 impl Error for EstablishError {
@@ -53,6 +73,7 @@ impl Error for EstablishError {
         match *self {
             EstablishError::GetSocketPathError(_) => "Couldn't determine i3's socket path",
             EstablishError::SocketError(_) => "Found i3's socket path but failed to connect",
+            EstablishError::Timeout => "Timed out while connecting to i3",
         }
     }
     fn cause(&self) -> Option<&dyn Error> {
@@ -60,11 +81,13 @@ impl Error for EstablishError {
             EstablishError::GetSocketPathError(ref e) | EstablishError::SocketError(ref e) => {
                 Some(e)
             }
+            EstablishError::Timeout => None,
         }
     }
 }
 
 impl fmt::Display for EstablishError {
+    #[allow(deprecated)]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.description())
     }
@@ -75,178 +98,664 @@ impl fmt::Display for EstablishError {
 pub enum MessageError {
     /// Network error sending the message.
     Send(io::Error),
+    /// Sending the message took longer than the write timeout set with
+    /// `I3Connection::set_write_timeout`.
+    SendTimedOut(io::Error),
     /// Network error receiving the response.
     Receive(io::Error),
+    /// The other end of the socket doesn't speak i3's protocol: its reply didn't start with the
+    /// "i3-ipc" magic string. Usually means the socket path points at something other than i3.
+    InvalidMagic {
+        /// The bytes received where the magic string should have been.
+        got: String,
+    },
     /// Got the response but couldn't parse the JSON.
     JsonCouldntParse(json::Error),
+    /// Received an event of a type this library doesn't know about.
+    UnknownEventType(u32),
+    /// Got a response that parsed as JSON but doesn't have the structure expected for the
+    /// request, such as an error object where an array was expected.
+    UnexpectedReply(String),
+    /// i3 is restarting, so no more events will arrive on this connection. Only returned by
+    /// `RestartAwareEventIterator`.
+    Restarting,
+    /// With strict parsing on (see `set_strict_parsing`), i3 sent a value for an enum field
+    /// that this library doesn't know.
+    UnknownEnumValue {
+        /// The name of the field, as i3 sends it.
+        field: &'static str,
+        /// The value i3 sent.
+        value: String,
+    },
+    /// With strict UTF-8 on (see `set_strict_utf8`), the payload of an event isn't valid UTF-8.
+    InvalidUtf8(str::Utf8Error),
+    /// Writing to the output failed. Only returned by `I3EventListener::log_events`.
+    Write(io::Error),
 }
 
 impl Error for MessageError {
     fn description(&self) -> &str {
         match *self {
             MessageError::Send(_) => "Network error while sending message to i3",
+            MessageError::SendTimedOut(_) => "Timed out while sending message to i3",
             MessageError::Receive(_) => "Network error while receiving message from i3",
+            MessageError::InvalidMagic { .. } => "The socket doesn't appear to belong to i3",
             MessageError::JsonCouldntParse(_) => {
                 "Got a response from i3 but couldn't parse the JSON"
             }
+            MessageError::UnknownEventType(_) => "Received an event of unknown type from i3",
+            MessageError::UnexpectedReply(_) => {
+                "Got a response from i3 that doesn't have the expected structure"
+            }
+            MessageError::Restarting => "i3 is restarting",
+            MessageError::UnknownEnumValue { .. } => {
+                "Got a value from i3 that this library doesn't know"
+            }
+            MessageError::InvalidUtf8(_) => "Got a message from i3 that isn't valid UTF-8",
+            MessageError::Write(_) => "Error while writing to the output",
         }
     }
     fn cause(&self) -> Option<&dyn Error> {
         match *self {
-            MessageError::Send(ref e) | MessageError::Receive(ref e) => Some(e),
+            MessageError::Send(ref e)
+            | MessageError::SendTimedOut(ref e)
+            | MessageError::Receive(ref e)
+            | MessageError::Write(ref e) => Some(e),
             MessageError::JsonCouldntParse(ref e) => Some(e),
+            MessageError::InvalidUtf8(ref e) => Some(e),
+            MessageError::InvalidMagic { .. }
+            | MessageError::UnknownEventType(_)
+            | MessageError::UnexpectedReply(_)
+            | MessageError::Restarting
+            | MessageError::UnknownEnumValue { .. } => None,
         }
     }
 }
 
 impl fmt::Display for MessageError {
+    #[allow(deprecated)]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.description())
     }
 }
 
-fn get_socket_path() -> io::Result<String> {
-    if let Ok(sockpath) = env::var("I3SOCK") {
-        return Ok(sockpath);
+/// An error turning JSON from i3 into one of the reply or event types.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// A field that's required is missing.
+    MissingField(&'static str),
+    /// A field doesn't have the expected type or structure.
+    InvalidField(&'static str),
+    /// With strict parsing on (see `set_strict_parsing`), an enum field has a value this
+    /// library doesn't know.
+    UnknownEnumValue {
+        /// The name of the field.
+        field: &'static str,
+        /// The value found.
+        value: String,
+    },
+}
+
+impl Error for ParseError {
+    fn description(&self) -> &str {
+        match *self {
+            ParseError::MissingField(_) => "A required field is missing",
+            ParseError::InvalidField(_) => "A field has an unexpected type or structure",
+            ParseError::UnknownEnumValue { .. } => "A field has a value this library doesn't know",
+        }
     }
-    // Sway support is an untested and unsupported feature
-    if let Ok(sockpath) = env::var("SWAYSOCK") {
-        return Ok(sockpath);
+}
+
+impl fmt::Display for ParseError {
+    #[allow(deprecated)]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::MissingField(name) | ParseError::InvalidField(name) => {
+                write!(f, "{}: {}", self.description(), name)
+            }
+            ParseError::UnknownEnumValue { field, ref value } => {
+                write!(f, "{}: {} = {:?}", self.description(), field, value)
+            }
+        }
     }
+}
 
-    let output = process::Command::new("i3").arg("--get-socketpath").output()?;
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout)
-            .trim_end_matches('\n')
-            .to_owned())
-    } else {
-        let prefix = "i3 --get-socketpath didn't return 0";
-        let error_text = if !output.stderr.is_empty() {
-            format!("{}. stderr: {:?}", prefix, output.stderr)
-        } else {
-            prefix.to_owned()
-        };
-        let error = io::Error::new(io::ErrorKind::Other, error_text);
-        Err(error)
+impl From<ParseError> for MessageError {
+    fn from(error: ParseError) -> MessageError {
+        match error {
+            ParseError::UnknownEnumValue { field, value } => {
+                MessageError::UnknownEnumValue { field, value }
+            }
+            error => MessageError::UnexpectedReply(error.to_string()),
+        }
     }
 }
 
-trait I3Funcs {
-    fn send_i3_message(&mut self, u32, &str) -> io::Result<()>;
-    fn receive_i3_message(&mut self) -> io::Result<(u32, String)>;
-    fn send_receive_i3_message<T: serde::de::DeserializeOwned>(
-        &mut self,
-        message_type: u32,
-        payload: &str,
-    ) -> Result<T, MessageError>;
+thread_local! {
+    static SOCKET_PATH_OVERRIDE: RefCell<Option<String>> = const { RefCell::new(None) };
+    static STRICT_PARSING: Cell<bool> = const { Cell::new(false) };
+    static STRICT_UTF8: Cell<bool> = const { Cell::new(false) };
 }
 
-impl I3Funcs for UnixStream {
-    fn send_i3_message(&mut self, message_type: u32, payload: &str) -> io::Result<()> {
-        let mut bytes = Vec::with_capacity(14 + payload.len());
-        bytes.extend("i3-ipc".bytes()); // 6 bytes
-        bytes.write_u32::<LittleEndian>(payload.len() as u32)?; // 4 bytes
-        bytes.write_u32::<LittleEndian>(message_type)?; // 4 bytes
-        bytes.extend(payload.bytes()); // payload.len() bytes
-        self.write_all(&bytes[..])
-    }
-
-    /// returns a tuple of (message type, payload)
-    fn receive_i3_message(&mut self) -> io::Result<(u32, String)> {
-        let mut magic_data = [0_u8; 6];
-        self.read_exact(&mut magic_data)?;
-        let magic_string = String::from_utf8_lossy(&magic_data);
-        if magic_string != "i3-ipc" {
-            let error_text = format!(
-                "unexpected magic string: expected 'i3-ipc' but got {}",
-                magic_string
-            );
-            return Err(io::Error::new(io::ErrorKind::Other, error_text));
+/// Makes replies and events parsed on the current thread fail on enum values this library
+/// doesn't know, rather than logging a warning and using the `Unknown` variant. Covers the
+/// type, border and layout of nodes, and the change and input type of events.
+///
+/// Replies and events received by a listener then fail with `MessageError::UnknownEnumValue`.
+/// Parsing an event with `FromStr` fails with a `json::Error` whose message names the field
+/// and value. Like
+/// `set_socket_path_override` this is thread-local, so it applies wherever the parsing
+/// happens, which is the thread reading from the connection.
+pub fn set_strict_parsing(strict: bool) {
+    STRICT_PARSING.with(|s| s.set(strict));
+}
+
+fn strict_parsing() -> bool {
+    STRICT_PARSING.with(|s| s.get())
+}
+
+/// Makes events received on the current thread fail with `MessageError::InvalidUtf8` when
+/// their payload isn't valid UTF-8, rather than replacing the invalid bytes with U+FFFD.
+/// Replies are always parsed straight from the bytes, so invalid UTF-8 in them already fails
+/// with `MessageError::JsonCouldntParse`. Like `set_strict_parsing` this is thread-local.
+pub fn set_strict_utf8(strict: bool) {
+    STRICT_UTF8.with(|s| s.set(strict));
+}
+
+fn strict_utf8() -> bool {
+    STRICT_UTF8.with(|s| s.get())
+}
+
+/// Makes connections established on the current thread use the socket at `path`, skipping the
+/// usual discovery. Pass `None` to go back to discovering the socket.
+///
+/// The override is thread-local: other threads keep discovering the socket as before, so tests
+/// running in parallel can each point at their own i3 without touching the process-wide
+/// `I3SOCK`.
+pub fn set_socket_path_override(path: Option<String>) {
+    SOCKET_PATH_OVERRIDE.with(|o| *o.borrow_mut() = path);
+}
+
+/// Describes how to find the path of i3's socket.
+///
+/// By default the `I3SOCK` and `SWAYSOCK` environment variables are checked, and if neither is
+/// set the path is asked of `i3 --get-socketpath`. A path set with `set_socket_path_override`
+/// takes precedence over all of these.
+#[derive(Debug, Clone)]
+pub struct SocketDiscovery {
+    env_vars: Option<Vec<String>>,
+    binary: String,
+}
+
+impl Default for SocketDiscovery {
+    fn default() -> SocketDiscovery {
+        SocketDiscovery {
+            env_vars: None,
+            binary: "i3".to_owned(),
+        }
+    }
+}
+
+impl SocketDiscovery {
+    /// The default way of finding i3's socket.
+    pub fn new() -> SocketDiscovery {
+        SocketDiscovery::default()
+    }
+
+    /// Checks the environment variable `name` instead of `I3SOCK` and `SWAYSOCK`. Can be
+    /// called multiple times, in which case the variables are checked in order.
+    pub fn env_var(mut self, name: &str) -> SocketDiscovery {
+        self.env_vars
+            .get_or_insert_with(Vec::new)
+            .push(name.to_owned());
+        self
+    }
+
+    /// Runs `binary --get-socketpath` instead of `i3 --get-socketpath`.
+    pub fn binary(mut self, binary: &str) -> SocketDiscovery {
+        self.binary = binary.to_owned();
+        self
+    }
+
+    fn socket_path(&self) -> io::Result<String> {
+        if let Some(path) = SOCKET_PATH_OVERRIDE.with(|o| o.borrow().clone()) {
+            return Ok(path);
+        }
+        match self.env_vars {
+            Some(ref names) => {
+                for name in names {
+                    if let Ok(sockpath) = env::var(name) {
+                        return Ok(sockpath);
+                    }
+                }
+            }
+            None => {
+                if let Ok(sockpath) = env::var("I3SOCK") {
+                    return Ok(sockpath);
+                }
+                // Sway support is an untested and unsupported feature
+                if let Ok(sockpath) = env::var("SWAYSOCK") {
+                    return Ok(sockpath);
+                }
+            }
         }
-        let payload_len = self.read_u32::<LittleEndian>()?;
-        let message_type = self.read_u32::<LittleEndian>()?;
-        let mut payload_data = vec![0_u8; payload_len as usize];
-        self.read_exact(&mut payload_data[..])?;
-        let payload_string = String::from_utf8_lossy(&payload_data).into_owned();
-        Ok((message_type, payload_string))
+
+        let output = process::Command::new(&self.binary)
+            .arg("--get-socketpath")
+            .output()?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout)
+                .trim_end_matches('\n')
+                .to_owned())
+        } else {
+            let prefix = format!("{} --get-socketpath didn't return 0", self.binary);
+            let error_text = if !output.stderr.is_empty() {
+                format!("{}. stderr: {:?}", prefix, output.stderr)
+            } else {
+                prefix
+            };
+            let error = io::Error::other(error_text);
+            Err(error)
+        }
+    }
+}
+
+/// The magic string every i3 message starts with.
+const DEFAULT_MAGIC: &str = "i3-ipc";
+
+/// Writes a single message to `writer`.
+fn write_i3_message<W: Write>(
+    writer: &mut W,
+    magic: &str,
+    message_type: u32,
+    payload: &str,
+) -> io::Result<()> {
+    write_i3_message_bytes(writer, magic, message_type, payload.as_bytes())
+}
+
+/// Like `write_i3_message`, but takes the payload as bytes.
+fn write_i3_message_bytes<W: Write>(
+    writer: &mut W,
+    magic: &str,
+    message_type: u32,
+    payload: &[u8],
+) -> io::Result<()> {
+    let len = payload_len(payload.len())?;
+    let mut bytes = Vec::with_capacity(magic.len() + 8 + payload.len());
+    bytes.extend(magic.bytes()); // magic.len() bytes
+    bytes.write_u32::<LittleEndian>(len)?; // 4 bytes
+    bytes.write_u32::<LittleEndian>(message_type)?; // 4 bytes
+    bytes.extend_from_slice(payload); // payload.len() bytes
+    writer.write_all(&bytes[..])
+}
+
+/// The length of a payload as the header encodes it, or an error if it doesn't fit in the
+/// header's 32 bits.
+fn payload_len(len: usize) -> io::Result<u32> {
+    if len as u64 > u64::from(u32::MAX) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("payload of {} bytes is too large for an i3 message", len),
+        ));
+    }
+    Ok(len as u32)
+}
+
+/// Reads a single message from `reader`, returning a tuple of (message type, payload).
+///
+/// Invalid UTF-8 in the payload is replaced rather than reported, unless `set_strict_utf8` is
+/// on.
+fn read_i3_message<R: Read>(reader: &mut R, magic: &str) -> io::Result<(u32, String)> {
+    let (message_type, payload) = read_i3_message_bytes(reader, magic)?;
+    Ok((message_type, payload_string(payload)?))
+}
+
+/// Decodes a payload read by `read_i3_message_bytes` the way `read_i3_message` does.
+fn payload_string(payload: Vec<u8>) -> io::Result<String> {
+    match String::from_utf8(payload) {
+        Ok(payload) => Ok(payload),
+        Err(e) if strict_utf8() => Err(io::Error::new(io::ErrorKind::InvalidData, e.utf8_error())),
+        Err(e) => Ok(String::from_utf8_lossy(e.as_bytes()).into_owned()),
+    }
+}
+
+/// Like `read_i3_message`, but leaves the payload as the bytes that were received.
+fn read_i3_message_bytes<R: Read>(reader: &mut R, magic: &str) -> io::Result<(u32, Vec<u8>)> {
+    let mut magic_data = vec![0_u8; magic.len()];
+    reader.read_exact(&mut magic_data)?;
+    if magic_data != magic.as_bytes() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            InvalidMagic {
+                expected: magic.to_owned(),
+                got: String::from_utf8_lossy(&magic_data).into_owned(),
+            },
+        ));
+    }
+    let payload_len = reader.read_u32::<LittleEndian>()?;
+    let message_type = reader.read_u32::<LittleEndian>()?;
+    let mut payload_data = vec![0_u8; payload_len as usize];
+    reader.read_exact(&mut payload_data[..])?;
+    Ok((message_type, payload_data))
+}
+
+/// The error inside the `io::Error` returned by `read_i3_message` when the magic string is wrong.
+#[derive(Debug)]
+struct InvalidMagic {
+    expected: String,
+    got: String,
+}
+
+impl Error for InvalidMagic {}
+
+impl fmt::Display for InvalidMagic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unexpected magic string: expected '{}' but got {}",
+            self.expected, self.got
+        )
+    }
+}
+
+/// Turns an error from `write_i3_message` into a `MessageError`, telling a write timeout apart
+/// from other network errors.
+fn send_error(e: io::Error) -> MessageError {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => MessageError::SendTimedOut(e),
+        _ => MessageError::Send(e),
+    }
+}
+
+/// Turns an error from `read_i3_message` into a `MessageError`, telling a wrong magic string
+/// apart from network errors.
+fn receive_error(e: io::Error) -> MessageError {
+    if let Some(utf8) = e
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<str::Utf8Error>())
+    {
+        return MessageError::InvalidUtf8(*utf8);
+    }
+    match e
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<InvalidMagic>())
+    {
+        Some(magic) => MessageError::InvalidMagic {
+            got: magic.got.clone(),
+        },
+        None => MessageError::Receive(e),
     }
+}
+
+/// The length of the first message in `buf` if all of it has been buffered, for messages
+/// starting with a magic string of `magic_len` bytes.
+fn buffered_message_len(buf: &[u8], magic_len: usize) -> Option<usize> {
+    let header_len = magic_len + 8;
+    if buf.len() < header_len {
+        return None;
+    }
+    let payload_len = (&buf[magic_len..magic_len + 4])
+        .read_u32::<LittleEndian>()
+        .ok()? as usize;
+    if buf.len() < header_len + payload_len {
+        return None;
+    }
+    Some(header_len + payload_len)
+}
+
+trait I3Funcs {
+    fn send_i3_message(&mut self, message_type: u32, payload: &str) -> io::Result<()>;
+
+    /// returns a tuple of (message type, payload), leaving the payload as the bytes that were
+    /// received.
+    fn receive_i3_message_bytes(&mut self) -> io::Result<(u32, Vec<u8>)>;
 
     fn send_receive_i3_message<T: serde::de::DeserializeOwned>(
         &mut self,
         message_type: u32,
         payload: &str,
     ) -> Result<T, MessageError> {
-        if let Err(e) = self.send_i3_message(message_type, payload) {
-            return Err(MessageError::Send(e));
-        }
-        let received = match self.receive_i3_message() {
-            Ok((received_type, payload)) => {
-                assert_eq!(message_type, received_type);
-                payload
+        self.send_i3_message(message_type, payload)
+            .map_err(send_error)?;
+        // parsed straight from the bytes, so invalid UTF-8 is an error rather than replaced
+        let received = match self.receive_i3_message_bytes() {
+            Ok((received_type, payload)) if received_type == message_type => payload,
+            Ok((received_type, _)) => {
+                return Err(MessageError::UnexpectedReply(format!(
+                    "expected a reply of type {} but got type {}",
+                    message_type, received_type
+                )));
             }
             Err(e) => {
-                return Err(MessageError::Receive(e));
+                return Err(receive_error(e));
             }
         };
-        match json::from_str(&received) {
+        match json::from_slice(&received) {
             Ok(v) => Ok(v),
             Err(e) => Err(MessageError::JsonCouldntParse(e)),
         }
     }
 }
 
+impl I3Funcs for UnixStream {
+    fn send_i3_message(&mut self, message_type: u32, payload: &str) -> io::Result<()> {
+        write_i3_message(self, DEFAULT_MAGIC, message_type, payload)
+    }
+
+    fn receive_i3_message_bytes(&mut self) -> io::Result<(u32, Vec<u8>)> {
+        read_i3_message_bytes(self, DEFAULT_MAGIC)
+    }
+}
+
+impl I3Funcs for I3EventListener {
+    fn send_i3_message(&mut self, message_type: u32, payload: &str) -> io::Result<()> {
+        write_i3_message(&mut self.stream, &self.magic, message_type, payload)
+    }
+
+    fn receive_i3_message_bytes(&mut self) -> io::Result<(u32, Vec<u8>)> {
+        read_i3_message_bytes(&mut self.stream, &self.magic)
+    }
+}
+
+impl I3Funcs for I3Connection {
+    fn send_i3_message(&mut self, message_type: u32, payload: &str) -> io::Result<()> {
+        write_i3_message(&mut self.stream, &self.magic, message_type, payload)
+    }
+
+    fn receive_i3_message_bytes(&mut self) -> io::Result<(u32, Vec<u8>)> {
+        read_i3_message_bytes(&mut self.stream, &self.magic)
+    }
+}
+
+/// the msgtype passed in should have its highest order bit stripped
+/// makes the i3 event
+fn build_event(msgtype: u32, payload: &str) -> Result<event::Event, MessageError> {
+    let parsed = match msgtype {
+        0 => event::WorkspaceEventInfo::parse(payload).map(event::Event::WorkspaceEvent),
+        1 => event::OutputEventInfo::parse(payload).map(event::Event::OutputEvent),
+        2 => event::ModeEventInfo::parse(payload).map(event::Event::ModeEvent),
+        3 => event::WindowEventInfo::parse(payload).map(event::Event::WindowEvent),
+        4 => event::BarConfigEventInfo::parse(payload).map(event::Event::BarConfigEvent),
+        5 => event::BindingEventInfo::parse(payload).map(event::Event::BindingEvent),
+
+        #[cfg(feature = "i3-4-14")]
+        6 => event::ShutdownEventInfo::parse(payload).map(event::Event::ShutdownEvent),
+
+        _ => return Err(MessageError::UnknownEventType(msgtype)),
+    };
+    parsed.map_err(|error| match error {
+        event::EventParseError::Parse(error @ ParseError::UnknownEnumValue { .. }) => {
+            MessageError::from(error)
+        }
+        error => MessageError::JsonCouldntParse(error.into()),
+    })
+}
+
 /// Iterates over events from i3.
 ///
 /// Each element may be `Err` or `Ok` (Err for an issue with the socket connection or data sent
 /// from i3).
 #[derive(Debug)]
 pub struct EventIterator<'a> {
-    stream: &'a mut UnixStream,
+    listener: &'a mut I3EventListener,
 }
 
 impl<'a> Iterator for EventIterator<'a> {
     type Item = Result<event::Event, MessageError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        /// the msgtype passed in should have its highest order bit stripped
-        /// makes the i3 event
-        fn build_event(msgtype: u32, payload: &str) -> Result<event::Event, json::Error> {
-            Ok(match msgtype {
-                0 => {
-                    event::Event::WorkspaceEvent(event::WorkspaceEventInfo::from_str(payload)?)
-                }
-                1 => event::Event::OutputEvent(event::OutputEventInfo::from_str(payload)?),
-                2 => event::Event::ModeEvent(event::ModeEventInfo::from_str(payload)?),
-                3 => event::Event::WindowEvent(event::WindowEventInfo::from_str(payload)?),
-                4 => {
-                    event::Event::BarConfigEvent(event::BarConfigEventInfo::from_str(payload)?)
-                }
-                5 => event::Event::BindingEvent(event::BindingEventInfo::from_str(payload)?),
+        Some(self.listener.read_event())
+    }
+}
 
-                #[cfg(feature = "i3-4-14")]
-                6 => event::Event::ShutdownEvent(event::ShutdownEventInfo::from_str(payload)?),
+/// Iterates over events from i3 along with the raw JSON of each one.
+///
+/// Each element may be `Err` or `Ok` (Err for an issue with the socket connection or data sent
+/// from i3).
+#[derive(Debug)]
+pub struct RawEventIterator<'a> {
+    listener: &'a mut I3EventListener,
+}
 
-                _ => unreachable!("received an event we aren't subscribed to!"),
-            })
-        }
+impl<'a> Iterator for RawEventIterator<'a> {
+    type Item = Result<(event::Event, String), MessageError>;
 
-        match self.stream.receive_i3_message() {
-            Ok((msgint, payload)) => {
-                // strip the highest order bit indicating it's an event.
-                let msgtype = (msgint << 1) >> 1;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.listener.read_raw_event())
+    }
+}
+
+/// Iterates over events from i3, signalling a restart of i3 instead of failing.
+///
+/// When i3 announces that it is restarting (with a shutdown event, which needs the `i3-4-14`
+/// feature) or closes the connection, this yields `Err(MessageError::Restarting)` once and
+/// then ends, so the caller can wait and reconnect rather than spin on errors.
+#[derive(Debug)]
+pub struct RestartAwareEventIterator<'a> {
+    listener: &'a mut I3EventListener,
+    restarting: bool,
+    closed: bool,
+}
+
+impl<'a> Iterator for RestartAwareEventIterator<'a> {
+    type Item = Result<event::Event, MessageError>;
 
-                Some(match build_event(msgtype, &payload) {
-                    Ok(event) => Ok(event),
-                    Err(e) => Err(MessageError::JsonCouldntParse(e)),
-                })
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.closed {
+            return None;
+        }
+        match self.listener.read_event() {
+            #[cfg(feature = "i3-4-14")]
+            Ok(event::Event::ShutdownEvent(ref info))
+                if info.change == event::inner::ShutdownChange::Restart =>
+            {
+                self.restarting = true;
+                Some(Err(MessageError::Restarting))
+            }
+            Err(MessageError::Receive(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                self.closed = true;
+                if self.restarting {
+                    None
+                } else {
+                    Some(Err(MessageError::Restarting))
+                }
             }
-            Err(e) => Some(Err(MessageError::Receive(e))),
+            // the dying socket may also be reset rather than closed cleanly
+            Err(MessageError::Receive(_)) if self.restarting => {
+                self.closed = true;
+                None
+            }
+            other => Some(other),
         }
     }
 }
 
-/// A subscription for `I3EventListener`
+/// Iterates over events from i3, owning the underlying connection.
+///
+/// Yields the same items as `EventIterator`, but since it doesn't borrow an `I3EventListener`
+/// it can be stored in a struct or returned from a function.
 #[derive(Debug)]
+pub struct OwnedEventIterator {
+    listener: I3EventListener,
+}
+
+impl Iterator for OwnedEventIterator {
+    type Item = Result<event::Event, MessageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.listener.read_event())
+    }
+}
+
+/// Keeps only the events of one `Event` variant, passing errors through.
+macro_rules! only_events {
+    ($iter:expr, $variant:ident) => {
+        $iter.filter_map(|e| match e {
+            Ok(event::Event::$variant(info)) => Some(Ok(info)),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        })
+    };
+}
+
+/// Adapters that narrow the iterator down to a single kind of event.
+///
+/// Events of any other kind are skipped, while errors are passed through unchanged.
+impl<'a> EventIterator<'a> {
+    /// Only yields workspace events.
+    pub fn workspace_events(
+        self,
+    ) -> impl Iterator<Item = Result<event::WorkspaceEventInfo, MessageError>> + 'a {
+        only_events!(self, WorkspaceEvent)
+    }
+
+    /// Only yields output events.
+    pub fn output_events(
+        self,
+    ) -> impl Iterator<Item = Result<event::OutputEventInfo, MessageError>> + 'a {
+        only_events!(self, OutputEvent)
+    }
+
+    /// Only yields mode events.
+    pub fn mode_events(
+        self,
+    ) -> impl Iterator<Item = Result<event::ModeEventInfo, MessageError>> + 'a {
+        only_events!(self, ModeEvent)
+    }
+
+    /// Only yields window events.
+    pub fn window_events(
+        self,
+    ) -> impl Iterator<Item = Result<event::WindowEventInfo, MessageError>> + 'a {
+        only_events!(self, WindowEvent)
+    }
+
+    /// Only yields barconfig_update events.
+    pub fn barconfig_events(
+        self,
+    ) -> impl Iterator<Item = Result<event::BarConfigEventInfo, MessageError>> + 'a {
+        only_events!(self, BarConfigEvent)
+    }
+
+    /// Only yields binding events.
+    pub fn binding_events(
+        self,
+    ) -> impl Iterator<Item = Result<event::BindingEventInfo, MessageError>> + 'a {
+        only_events!(self, BindingEvent)
+    }
+
+    /// Only yields shutdown events.
+    #[cfg(feature = "i3-4-14")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-14")))]
+    pub fn shutdown_events(
+        self,
+    ) -> impl Iterator<Item = Result<event::ShutdownEventInfo, MessageError>> + 'a {
+        only_events!(self, ShutdownEvent)
+    }
+}
+
+/// A subscription for `I3EventListener`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Subscription {
     Workspace,
     Output,
@@ -259,440 +768,4354 @@ pub enum Subscription {
     Shutdown,
 }
 
-/// Abstraction over an ipc socket to i3. Handles events.
-#[derive(Debug)]
-pub struct I3EventListener {
-    stream: UnixStream,
-}
-
-impl I3EventListener {
-    /// Establishes the IPC connection.
-    pub fn connect() -> Result<I3EventListener, EstablishError> {
-        match get_socket_path() {
-            Ok(path) => match UnixStream::connect(path) {
-                Ok(stream) => Ok(I3EventListener { stream }),
-                Err(error) => Err(EstablishError::SocketError(error)),
-            },
-            Err(error) => Err(EstablishError::GetSocketPathError(error)),
+impl Subscription {
+    /// The name i3 uses for this kind of event.
+    fn name(&self) -> &'static str {
+        match *self {
+            Subscription::Workspace => "workspace",
+            Subscription::Output => "output",
+            Subscription::Mode => "mode",
+            Subscription::Window => "window",
+            Subscription::BarConfig => "barconfig_update",
+            Subscription::Binding => "binding",
+            #[cfg(feature = "i3-4-14")]
+            Subscription::Shutdown => "shutdown",
         }
     }
 
-    /// Subscribes your connection to certain events.
-    pub fn subscribe(&mut self, events: &[Subscription]) -> Result<reply::Subscribe, MessageError> {
-        let json = "[ ".to_owned()
-            + &events
-                .iter()
-                .map(|s| match *s {
-                    Subscription::Workspace => "\"workspace\"",
-                    Subscription::Output => "\"output\"",
-                    Subscription::Mode => "\"mode\"",
-                    Subscription::Window => "\"window\"",
-                    Subscription::BarConfig => "\"barconfig_update\"",
-                    Subscription::Binding => "\"binding\"",
-                    #[cfg(feature = "i3-4-14")]
-                    Subscription::Shutdown => "\"shutdown\"",
-                })
-                .collect::<Vec<_>>()
-                .join(", ")[..]
-            + " ]";
-        let j: json::Value = self.stream.send_receive_i3_message(2, &json)?;
-        let is_success = j.get("success").unwrap().as_bool().unwrap();
-        Ok(reply::Subscribe {
-            success: is_success,
-        })
+    /// Every subscription supported by the enabled features.
+    pub fn all() -> &'static [Subscription] {
+        &[
+            Subscription::Workspace,
+            Subscription::Output,
+            Subscription::Mode,
+            Subscription::Window,
+            Subscription::BarConfig,
+            Subscription::Binding,
+            #[cfg(feature = "i3-4-14")]
+            Subscription::Shutdown,
+        ]
     }
+}
 
-    /// Iterate over subscribed events forever.
-    pub fn listen(&mut self) -> EventIterator {
-        EventIterator {
-            stream: &mut self.stream,
-        }
+/// Serializes as the name i3 uses for the event, such as `"barconfig_update"`.
+impl serde::Serialize for Subscription {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
     }
 }
 
-/// Abstraction over an ipc socket to i3. Handles messages/replies.
-#[derive(Debug)]
-pub struct I3Connection {
-    stream: UnixStream,
+impl<'de> serde::Deserialize<'de> for Subscription {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let name = String::deserialize(deserializer)?;
+        Subscription::all()
+            .iter()
+            .find(|s| s.name() == name)
+            .cloned()
+            .ok_or_else(|| D::Error::custom(format!("unknown subscription `{}`", name)))
+    }
 }
 
-impl I3Connection {
-    /// Establishes the IPC connection.
-    pub fn connect() -> Result<I3Connection, EstablishError> {
-        match get_socket_path() {
-            Ok(path) => match UnixStream::connect(path) {
-                Ok(stream) => Ok(I3Connection { stream }),
-                Err(error) => Err(EstablishError::SocketError(error)),
-            },
-            Err(error) => Err(EstablishError::GetSocketPathError(error)),
-        }
+/// Criteria selecting the windows a command applies to, as in `[class="mpv"] floating enable`.
+/// Values are quoted for you; for `class`, `instance`, `title` and `window_role` i3 treats
+/// them as regular expressions. Displays as the bracketed selector.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Criteria {
+    criteria: Vec<(&'static str, String)>,
+}
+
+impl Criteria {
+    /// Criteria matching every window.
+    pub fn new() -> Criteria {
+        Criteria::default()
     }
 
-    #[deprecated(since = "0.8.0", note = "Renamed to run_command")]
-    pub fn command(&mut self, string: &str) -> Result<reply::Command, MessageError> {
-        self.run_command(string)
+    fn with(mut self, key: &'static str, value: String) -> Criteria {
+        self.criteria.push((key, value));
+        self
     }
 
-    /// The payload of the message is a command for i3 (like the commands you can bind to keys
-    /// in the configuration file) and will be executed directly after receiving it.
-    pub fn run_command(&mut self, string: &str) -> Result<reply::Command, MessageError> {
-        let j: json::Value = self.stream.send_receive_i3_message(0, string)?;
-        let commands = j.as_array().unwrap();
-        let vec: Vec<_> = commands
-            .iter()
-            .map(|c| reply::CommandOutcome {
-                success: c.get("success").unwrap().as_bool().unwrap(),
-                error: match c.get("error") {
-                    Some(val) => Some(val.as_str().unwrap().to_owned()),
-                    None => None,
-                },
-            })
-            .collect();
+    /// Windows whose X11 class matches `class`.
+    pub fn class(self, class: &str) -> Criteria {
+        self.with("class", quote(class))
+    }
 
-        Ok(reply::Command { outcomes: vec })
+    /// Windows whose X11 instance matches `instance`.
+    pub fn instance(self, instance: &str) -> Criteria {
+        self.with("instance", quote(instance))
     }
 
-    /// Gets the current workspaces.
-    pub fn get_workspaces(&mut self) -> Result<reply::Workspaces, MessageError> {
-        let j: json::Value = self.stream.send_receive_i3_message(1, "")?;
-        let jworkspaces = j.as_array().unwrap();
-        let workspaces: Vec<_> = jworkspaces
-            .iter()
-            .map(|w| reply::Workspace {
-                num: w.get("num").unwrap().as_i64().unwrap() as i32,
-                name: w.get("name").unwrap().as_str().unwrap().to_owned(),
-                visible: w.get("visible").unwrap().as_bool().unwrap(),
-                focused: w.get("focused").unwrap().as_bool().unwrap(),
-                urgent: w.get("urgent").unwrap().as_bool().unwrap(),
-                rect: common::build_rect(w.get("rect").unwrap()),
-                output: w.get("output").unwrap().as_str().unwrap().to_owned(),
-            })
-            .collect();
-        Ok(reply::Workspaces { workspaces })
+    /// Windows whose title matches `title`.
+    pub fn title(self, title: &str) -> Criteria {
+        self.with("title", quote(title))
     }
 
-    /// Gets the current outputs.
-    pub fn get_outputs(&mut self) -> Result<reply::Outputs, MessageError> {
-        let j: json::Value = self.stream.send_receive_i3_message(3, "")?;
-        let joutputs = j.as_array().unwrap();
-        let outputs: Vec<_> = joutputs
-            .iter()
-            .map(|o| reply::Output {
-                name: o.get("name").unwrap().as_str().unwrap().to_owned(),
-                #[cfg(feature = "sway-1-1")]
-                make: o.get("make").unwrap().as_str().unwrap().to_owned(),
-                #[cfg(feature = "sway-1-1")]
-                model: o.get("model").unwrap().as_str().unwrap().to_owned(),
-                #[cfg(feature = "sway-1-1")]
-                serial: o.get("serial").unwrap().as_str().unwrap().to_owned(),
-                #[cfg(feature = "sway-1-1")]
-                scale: o.get("scale").map(|s| s.as_f64().unwrap().to_owned()),
-                #[cfg(feature = "sway-1-1")]
-                subpixel_hinting: o.get("subpixel_hinting").map(|s| s.as_str() .unwrap().to_owned()),
-                #[cfg(feature = "sway-1-1")]
-                transform: o.get("transform").map(|s| s.as_str().unwrap().to_owned()),
-                #[cfg(feature = "sway-1-1")]
-                modes: common::build_modes(o.get("modes").unwrap()),
-                #[cfg(feature = "sway-1-1")]
-                current_mode: o.get("current_mode").map(|s| common::build_mode(s)),
-                active: o.get("active").unwrap().as_bool().unwrap(),
-                primary: o.get("primary").unwrap().as_bool().unwrap(),
-                current_workspace: match o.get("current_workspace").unwrap().clone() {
-                    json::Value::String(c_w) => Some(c_w),
-                    json::Value::Null => None,
-                    _ => unreachable!(),
-                },
-                #[cfg(feature = "sway-1-1")]
-                dpms: o.get("dpms").unwrap().as_bool().unwrap(),
-                rect: common::build_rect(o.get("rect").unwrap()),
-            })
-            .collect();
-        Ok(reply::Outputs { outputs })
+    /// Windows whose X11 window role matches `role`.
+    pub fn window_role(self, role: &str) -> Criteria {
+        self.with("window_role", quote(role))
     }
 
-    /// Gets the layout tree. i3 uses a tree as data structure which includes every container.
-    pub fn get_tree(&mut self) -> Result<reply::Node, MessageError> {
-        let val: json::Value = self.stream.send_receive_i3_message(4, "")?;
-        Ok(common::build_tree(&val))
+    /// The window with the X11 window id `id`.
+    pub fn id(self, id: u32) -> Criteria {
+        self.with("id", id.to_string())
     }
 
-    /// Gets a list of marks (identifiers for containers to easily jump to them later).
-    pub fn get_marks(&mut self) -> Result<reply::Marks, MessageError> {
-        let marks: Vec<String> = self.stream.send_receive_i3_message(5, "")?;
+    /// The container with the id `id`, as in `reply::Node::id`.
+    pub fn con_id(self, id: i64) -> Criteria {
+        self.with("con_id", id.to_string())
+    }
+
+    /// Containers with a mark matching `mark`.
+    pub fn con_mark(self, mark: &str) -> Criteria {
+        self.with("con_mark", quote(mark))
+    }
+
+    /// Windows on a workspace whose name matches `workspace`.
+    pub fn workspace(self, workspace: &str) -> Criteria {
+        self.with("workspace", quote(workspace))
+    }
+}
+
+impl fmt::Display for Criteria {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, &(key, ref value)) in self.criteria.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}={}", key, value)?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// Settings for an output, to apply with `I3Connection::configure_output`. Settings that
+/// aren't set are left as they are.
+#[cfg(feature = "sway-1-1")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway-1-1")))]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutputConfig {
+    enable: Option<bool>,
+    mode: Option<(i32, i32, Option<f64>)>,
+    position: Option<(i32, i32)>,
+    scale: Option<f64>,
+    transform: Option<reply::Transform>,
+}
+
+#[cfg(feature = "sway-1-1")]
+impl OutputConfig {
+    /// No settings.
+    pub fn new() -> OutputConfig {
+        OutputConfig::default()
+    }
+
+    /// Turns the output on.
+    pub fn enable(mut self) -> OutputConfig {
+        self.enable = Some(true);
+        self
+    }
+
+    /// Turns the output off.
+    pub fn disable(mut self) -> OutputConfig {
+        self.enable = Some(false);
+        self
+    }
+
+    /// Sets the resolution, and the refresh rate in Hz if given.
+    pub fn mode(mut self, width: i32, height: i32, refresh_hz: Option<f64>) -> OutputConfig {
+        self.mode = Some((width, height, refresh_hz));
+        self
+    }
+
+    /// Places the top left corner of the output at `x`, `y` in the layout.
+    pub fn position(mut self, x: i32, y: i32) -> OutputConfig {
+        self.position = Some((x, y));
+        self
+    }
+
+    /// Sets the scale factor.
+    pub fn scale(mut self, scale: f64) -> OutputConfig {
+        self.scale = Some(scale);
+        self
+    }
+
+    /// Rotates and flips the output. `Transform::Unknown` is ignored.
+    pub fn transform(mut self, transform: reply::Transform) -> OutputConfig {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// The `output` command applying these settings to the output called `name`.
+    fn command(&self, name: &str) -> String {
+        let mut command = format!("output {}", quote(name));
+        match self.enable {
+            Some(true) => command.push_str(" enable"),
+            Some(false) => command.push_str(" disable"),
+            None => {}
+        }
+        if let Some((width, height, refresh)) = self.mode {
+            command.push_str(&format!(" mode {}x{}", width, height));
+            if let Some(refresh) = refresh {
+                command.push_str(&format!("@{}Hz", refresh));
+            }
+        }
+        if let Some((x, y)) = self.position {
+            command.push_str(&format!(" position {} {}", x, y));
+        }
+        if let Some(scale) = self.scale {
+            command.push_str(&format!(" scale {}", scale));
+        }
+        let transform = match self.transform {
+            Some(reply::Transform::Normal) => Some("normal"),
+            Some(reply::Transform::Rotate90) => Some("90"),
+            Some(reply::Transform::Rotate180) => Some("180"),
+            Some(reply::Transform::Rotate270) => Some("270"),
+            Some(reply::Transform::Flipped) => Some("flipped"),
+            Some(reply::Transform::Flipped90) => Some("flipped-90"),
+            Some(reply::Transform::Flipped180) => Some("flipped-180"),
+            Some(reply::Transform::Flipped270) => Some("flipped-270"),
+            Some(reply::Transform::Unknown) | None => None,
+        };
+        if let Some(transform) = transform {
+            command.push_str(&format!(" transform {}", transform));
+        }
+        command
+    }
+}
+
+/// Quotes an argument for use in an i3 command, so it may contain spaces, quotes or
+/// backslashes.
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Whether `error` means the other end closed the connection.
+fn is_connection_closed(error: &MessageError) -> bool {
+    match *error {
+        MessageError::Send(ref e) | MessageError::Receive(ref e) => matches!(
+            e.kind(),
+            io::ErrorKind::BrokenPipe
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
+
+/// Returns the elements of a reply that should be an array, or a `MessageError` describing
+/// what was received instead.
+fn expect_array(j: &json::Value) -> Result<&Vec<json::Value>, MessageError> {
+    match *j {
+        json::Value::Array(ref elements) => Ok(elements),
+        _ => Err(unexpected_reply(j, "an array")),
+    }
+}
+
+/// The error for a reply that isn't `expected`, including i3's error message if it sent one.
+fn unexpected_reply(j: &json::Value, expected: &str) -> MessageError {
+    match j.get("error").and_then(|e| e.as_str()) {
+        Some(error) => MessageError::UnexpectedReply(format!(
+            "expected {} but i3 reported an error: {}",
+            expected, error
+        )),
+        None => MessageError::UnexpectedReply(format!("expected {} but got {}", expected, j)),
+    }
+}
+
+/// Builds the JSON array of event names sent with the `subscribe` request.
+fn subscribe_payload(events: &[Subscription]) -> String {
+    let names: Vec<&str> = events.iter().map(Subscription::name).collect();
+    json::to_string(&names).unwrap()
+}
+
+/// Abstraction over an ipc socket to i3. Handles events.
+#[derive(Debug)]
+pub struct I3EventListener {
+    stream: UnixStream,
+    subscriptions: Vec<Subscription>,
+    /// Bytes read by `poll_events` that don't yet form a complete message.
+    pending: Vec<u8>,
+    magic: String,
+}
+
+impl I3EventListener {
+    /// Establishes the IPC connection.
+    pub fn connect() -> Result<I3EventListener, EstablishError> {
+        I3EventListener::connect_with(&SocketDiscovery::default())
+    }
+
+    /// Establishes the IPC connection, finding i3's socket as described by `discovery`.
+    pub fn connect_with(discovery: &SocketDiscovery) -> Result<I3EventListener, EstablishError> {
+        match discovery.socket_path() {
+            Ok(path) => match UnixStream::connect(path) {
+                Ok(stream) => Ok(I3EventListener::from_stream(stream)),
+                Err(error) => Err(EstablishError::SocketError(error)),
+            },
+            Err(error) => Err(EstablishError::GetSocketPathError(error)),
+        }
+    }
+
+    fn from_stream(stream: UnixStream) -> I3EventListener {
+        I3EventListener {
+            stream,
+            subscriptions: Vec::new(),
+            pending: Vec::new(),
+            magic: DEFAULT_MAGIC.to_owned(),
+        }
+    }
+
+    /// Uses `magic` instead of "i3-ipc" as the magic string that starts every message, for
+    /// talking to forks of i3 that use a different one.
+    pub fn with_magic(mut self, magic: &str) -> I3EventListener {
+        self.magic = magic.to_owned();
+        self
+    }
+
+    /// Subscribes your connection to certain events.
+    ///
+    /// Subscriptions are additive: i3 keeps every event type a connection has subscribed to,
+    /// so calling this again augments rather than replaces the earlier subscriptions.
+    pub fn subscribe(&mut self, events: &[Subscription]) -> Result<reply::Subscribe, MessageError> {
+        for event in events {
+            if !self.subscriptions.contains(event) {
+                self.subscriptions.push(*event);
+            }
+        }
+        self.send_subscribe(events)
+    }
+
+    /// Subscribes your connection to every event supported by the enabled features.
+    pub fn subscribe_all(&mut self) -> Result<reply::Subscribe, MessageError> {
+        self.subscribe(Subscription::all())
+    }
+
+    /// Adds a single subscription to the ones already made on this connection.
+    ///
+    /// The full set of subscriptions tracked so far is sent to i3 again. Since subscriptions
+    /// are additive this augments the existing subscriptions, it never removes any.
+    pub fn add_subscription(
+        &mut self,
+        sub: Subscription,
+    ) -> Result<reply::Subscribe, MessageError> {
+        if !self.subscriptions.contains(&sub) {
+            self.subscriptions.push(sub);
+        }
+        let subscriptions = self.subscriptions.clone();
+        self.send_subscribe(&subscriptions)
+    }
+
+    /// The events this connection has subscribed to so far.
+    pub fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    fn send_subscribe(
+        &mut self,
+        events: &[Subscription],
+    ) -> Result<reply::Subscribe, MessageError> {
+        let json = subscribe_payload(events);
+        self.send_i3_message(2, &json).map_err(send_error)?;
+        // events may already be on their way when subscribing again, so queue any that arrive
+        // before the reply for `listen` and friends to return later.
+        let mut early_events = Vec::new();
+        let payload = loop {
+            let (message_type, payload) = self.read_message_bytes().map_err(receive_error)?;
+            if message_type >> 31 == 1 {
+                write_i3_message_bytes(&mut early_events, &self.magic, message_type, &payload)
+                    .map_err(MessageError::Receive)?;
+            } else if message_type == 2 {
+                break payload;
+            } else {
+                return Err(MessageError::UnexpectedReply(format!(
+                    "expected a reply of type 2 but got type {}",
+                    message_type
+                )));
+            }
+        };
+        early_events.append(&mut self.pending);
+        self.pending = early_events;
+
+        let j: json::Value = json::from_slice(&payload).map_err(MessageError::JsonCouldntParse)?;
+        let is_success = j
+            .get("success")
+            .and_then(|s| s.as_bool())
+            .ok_or_else(|| unexpected_reply(&j, "a subscribe reply"))?;
+        Ok(reply::Subscribe {
+            success: is_success,
+            error: j
+                .get("error")
+                .and_then(|e| e.as_str())
+                .map(|e| e.to_owned()),
+        })
+    }
+
+    /// Iterate over subscribed events forever.
+    pub fn listen(&mut self) -> EventIterator<'_> {
+        EventIterator { listener: self }
+    }
+
+    /// Iterate over subscribed events forever, yielding each event along with the JSON it was
+    /// parsed from. Handy for debugging or reporting a field that was parsed incorrectly.
+    pub fn listen_raw(&mut self) -> RawEventIterator<'_> {
+        RawEventIterator { listener: self }
+    }
+
+    /// Blocks until the next subscribed event arrives and returns it.
+    pub fn next_event(&mut self) -> Result<event::Event, MessageError> {
+        self.read_event()
+    }
+
+    /// Iterate over subscribed events until i3 restarts or closes the connection, which is
+    /// signalled with `Err(MessageError::Restarting)`. See `RestartAwareEventIterator`.
+    pub fn listen_restart_aware(&mut self) -> RestartAwareEventIterator<'_> {
+        RestartAwareEventIterator {
+            listener: self,
+            restarting: false,
+            closed: false,
+        }
+    }
+
+    /// Iterate over subscribed events forever, consuming the listener.
+    pub fn into_events(self) -> OwnedEventIterator {
+        OwnedEventIterator { listener: self }
+    }
+
+    /// Subscribes to every event and writes each one to `writer` in its `Display` form, one
+    /// per line, for quick diagnostics.
+    ///
+    /// Runs until something fails and returns the error. Errors writing to `writer`, such as
+    /// when the output is piped into a program that exited, are returned as
+    /// `MessageError::Write`.
+    pub fn log_events<W: Write>(&mut self, mut writer: W) -> Result<(), MessageError> {
+        self.subscribe_all()?;
+        loop {
+            let event = self.read_event()?;
+            writeln!(writer, "{}", event).map_err(MessageError::Write)?;
+        }
+    }
+
+    /// Reads events until one matches `pred` and returns it.
+    ///
+    /// Every event read before the matching one is dropped. Returns early with the first error
+    /// encountered.
+    pub fn wait_for<F: FnMut(&event::Event) -> bool>(
+        &mut self,
+        mut pred: F,
+    ) -> Result<event::Event, MessageError> {
+        loop {
+            let event = self.read_event()?;
+            if pred(&event) {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Returns every event that has already arrived, without blocking.
+    ///
+    /// Reading stops as soon as the socket has no more data available. A message that has
+    /// only partially arrived is kept and completed by a later call (or by `listen`).
+    pub fn poll_events(&mut self) -> Vec<Result<event::Event, MessageError>> {
+        let mut events = Vec::new();
+        if let Err(e) = self.stream.set_nonblocking(true) {
+            events.push(Err(MessageError::Receive(e)));
+            return events;
+        }
+        let mut read_error = None;
+        let mut buf = [0_u8; 4096];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    read_error = Some(io::Error::from(io::ErrorKind::UnexpectedEof));
+                    break;
+                }
+                Ok(n) => self.pending.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    read_error = Some(e);
+                    break;
+                }
+            }
+        }
+        if let Err(e) = self.stream.set_nonblocking(false) {
+            read_error.get_or_insert(e);
+        }
+
+        while let Some(event) = self.pop_buffered_event() {
+            events.push(event);
+        }
+        if let Some(e) = read_error {
+            events.push(Err(MessageError::Receive(e)));
+        }
+        events
+    }
+
+    /// Waits for the next event until `deadline`, returning `Ok(None)` if none arrived by then.
+    ///
+    /// Handy for loops that also have timers to run. A message that has only partially arrived
+    /// by the deadline is kept and completed by a later call (or by `listen`).
+    pub fn next_event_deadline(
+        &mut self,
+        deadline: Instant,
+    ) -> Result<Option<event::Event>, MessageError> {
+        let mut buf = [0_u8; 4096];
+        loop {
+            if let Some(event) = self.pop_buffered_event() {
+                return event.map(Some);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            self.stream
+                .set_read_timeout(Some(deadline - now))
+                .map_err(MessageError::Receive)?;
+            let read = self.stream.read(&mut buf);
+            self.stream
+                .set_read_timeout(None)
+                .map_err(MessageError::Receive)?;
+            match read {
+                Ok(0) => {
+                    return Err(MessageError::Receive(io::Error::from(
+                        io::ErrorKind::UnexpectedEof,
+                    )))
+                }
+                Ok(n) => self.pending.extend_from_slice(&buf[..n]),
+                Err(ref e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock
+                            | io::ErrorKind::TimedOut
+                            | io::ErrorKind::Interrupted
+                    ) => {}
+                Err(e) => return Err(MessageError::Receive(e)),
+            }
+        }
+    }
+
+    /// Takes the first message out of the bytes buffered by `poll_events` or
+    /// `next_event_deadline` and parses it, if all of it has arrived.
+    fn pop_buffered_event(&mut self) -> Option<Result<event::Event, MessageError>> {
+        let len = buffered_message_len(&self.pending, self.magic.len())?;
+        let message = read_i3_message(&mut &self.pending[..len], &self.magic);
+        self.pending.drain(..len);
+        Some(match message {
+            // strip the highest order bit indicating it's an event.
+            Ok((msgint, payload)) => build_event((msgint << 1) >> 1, &payload),
+            Err(e) => Err(receive_error(e)),
+        })
+    }
+
+    /// Blocks until the next event arrives and parses it, starting with any bytes already
+    /// buffered by `poll_events` or `next_event_deadline`.
+    fn read_event(&mut self) -> Result<event::Event, MessageError> {
+        self.read_raw_event().map(|(event, _)| event)
+    }
+
+    /// Reads the next whole message, starting with any bytes already buffered by `poll_events`
+    /// or `next_event_deadline`.
+    fn read_message_bytes(&mut self) -> io::Result<(u32, Vec<u8>)> {
+        if self.pending.is_empty() {
+            return self.receive_i3_message_bytes();
+        }
+        let mut reader = (&self.pending[..]).chain(&mut self.stream);
+        let received = read_i3_message_bytes(&mut reader, &self.magic);
+        let consumed = self.pending.len() - reader.into_inner().0.len();
+        self.pending.drain(..consumed);
+        received
+    }
+
+    /// Like `read_event`, but also returns the JSON payload the event was parsed from.
+    fn read_raw_event(&mut self) -> Result<(event::Event, String), MessageError> {
+        let received = self
+            .read_message_bytes()
+            .and_then(|(msgint, payload)| Ok((msgint, payload_string(payload)?)));
+        match received {
+            Ok((msgint, payload)) => {
+                // strip the highest order bit indicating it's an event.
+                let msgtype = (msgint << 1) >> 1;
+
+                build_event(msgtype, &payload).map(|event| (event, payload))
+            }
+            Err(e) => Err(receive_error(e)),
+        }
+    }
+
+    /// Closes the connection. Unlike dropping the listener this reports any error that occurs
+    /// while shutting down the socket.
+    pub fn shutdown(self) -> io::Result<()> {
+        self.stream.shutdown(Shutdown::Both)
+    }
+}
+
+impl AsRawFd for I3EventListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+impl AsFd for I3EventListener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.stream.as_fd()
+    }
+}
+
+/// Abstraction over an ipc socket to i3. Handles messages/replies.
+#[derive(Debug)]
+pub struct I3Connection {
+    stream: UnixStream,
+    magic: String,
+    /// How the socket was found, for finding it again when reconnecting.
+    discovery: SocketDiscovery,
+}
+
+impl I3Connection {
+    /// Establishes the IPC connection.
+    pub fn connect() -> Result<I3Connection, EstablishError> {
+        I3Connection::connect_with(&SocketDiscovery::default())
+    }
+
+    /// Establishes the IPC connection, finding i3's socket as described by `discovery`.
+    pub fn connect_with(discovery: &SocketDiscovery) -> Result<I3Connection, EstablishError> {
+        match discovery.socket_path() {
+            Ok(path) => match UnixStream::connect(path) {
+                Ok(stream) => Ok(I3Connection {
+                    discovery: discovery.clone(),
+                    ..I3Connection::from_stream(stream)
+                }),
+                Err(error) => Err(EstablishError::SocketError(error)),
+            },
+            Err(error) => Err(EstablishError::GetSocketPathError(error)),
+        }
+    }
+
+    /// Establishes the IPC connection, giving up with `EstablishError::Timeout` if finding the
+    /// socket path and connecting to it take longer than `timeout`.
+    ///
+    /// Neither step can be interrupted, so the attempt carries on in a background thread after
+    /// a timeout and its connection, if it ever succeeds, is dropped.
+    pub fn connect_timeout(timeout: Duration) -> Result<I3Connection, EstablishError> {
+        I3Connection::connect_timeout_with(SocketDiscovery::default(), timeout)
+    }
+
+    fn connect_timeout_with(
+        discovery: SocketDiscovery,
+        timeout: Duration,
+    ) -> Result<I3Connection, EstablishError> {
+        I3Connection::connect_timeout_using(move || I3Connection::connect_with(&discovery), timeout)
+    }
+
+    /// Runs `connect` in a background thread, giving up on it after `timeout`.
+    fn connect_timeout_using<F>(
+        connect: F,
+        timeout: Duration,
+    ) -> Result<I3Connection, EstablishError>
+    where
+        F: FnOnce() -> Result<I3Connection, EstablishError> + Send + 'static,
+    {
+        // the override is thread-local, so hand it to the thread doing the connecting
+        let path_override = SOCKET_PATH_OVERRIDE.with(|o| o.borrow().clone());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            set_socket_path_override(path_override);
+            let _ = sender.send(connect());
+        });
+        match receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(_) => Err(EstablishError::Timeout),
+        }
+    }
+
+    /// Establishes the IPC connection, making up to `attempts` tries with `delay` in between.
+    /// `attempts` includes the first try, and at least one try is always made, so passing 0
+    /// behaves like passing 1.
+    ///
+    /// Useful on session startup, when i3 might not have created its socket yet. Both finding
+    /// the socket path and connecting to it are retried; the error from the last attempt is
+    /// returned if none succeed.
+    pub fn connect_retry(attempts: u32, delay: Duration) -> Result<I3Connection, EstablishError> {
+        I3Connection::connect_retry_with(&SocketDiscovery::default(), attempts, delay)
+    }
+
+    fn connect_retry_with(
+        discovery: &SocketDiscovery,
+        attempts: u32,
+        delay: Duration,
+    ) -> Result<I3Connection, EstablishError> {
+        let mut attempt = 1;
+        loop {
+            match I3Connection::connect_with(discovery) {
+                Err(_) if attempt < attempts => {
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn from_stream(stream: UnixStream) -> I3Connection {
+        I3Connection {
+            stream,
+            magic: DEFAULT_MAGIC.to_owned(),
+            discovery: SocketDiscovery::default(),
+        }
+    }
+
+    /// Uses `magic` instead of "i3-ipc" as the magic string that starts every message, for
+    /// talking to forks of i3 that use a different one.
+    pub fn with_magic(mut self, magic: &str) -> I3Connection {
+        self.magic = magic.to_owned();
+        self
+    }
+
+    #[deprecated(since = "0.8.0", note = "Renamed to run_command")]
+    pub fn command(&mut self, string: &str) -> Result<reply::Command, MessageError> {
+        self.run_command(string)
+    }
+
+    /// The payload of the message is a command for i3 (like the commands you can bind to keys
+    /// in the configuration file) and will be executed directly after receiving it.
+    pub fn run_command(&mut self, string: &str) -> Result<reply::Command, MessageError> {
+        let j: json::Value = self.send_receive_i3_message(0, string)?;
+        let commands = expect_array(&j)?;
+        let vec = commands
+            .iter()
+            .map(common::build_command_outcome)
+            .collect::<Result<_, _>>()?;
+
+        Ok(reply::Command { outcomes: vec })
+    }
+
+    /// Like `run_command`, but if i3 closed the connection, as it does when restarting, this
+    /// discovers the socket again, reconnects and retries the command once. The socket is found
+    /// with the same `SocketDiscovery` the connection was made with, and the magic string set
+    /// with `with_magic` is kept.
+    ///
+    /// If reconnecting fails, the original error is returned. The command may have run before
+    /// the connection closed, so only use this for commands that are safe to run twice.
+    pub fn run_command_reconnecting(
+        &mut self,
+        string: &str,
+    ) -> Result<reply::Command, MessageError> {
+        let error = match self.run_command(string) {
+            Err(e) => e,
+            result => return result,
+        };
+        if !is_connection_closed(&error) {
+            return Err(error);
+        }
+        match I3Connection::connect_with(&self.discovery) {
+            Ok(connection) => self.stream = connection.stream,
+            Err(_) => return Err(error),
+        }
+        self.run_command(string)
+    }
+
+    /// Runs each of `cmds` as a separate request and returns their replies in order.
+    ///
+    /// Unlike joining the commands with `;`, every command gets its own reply, and a command
+    /// that fails to parse doesn't keep the later ones from running. Stops at the first request
+    /// that fails.
+    pub fn run_commands(&mut self, cmds: &[&str]) -> Result<Vec<reply::Command>, MessageError> {
+        cmds.iter().map(|cmd| self.run_command(cmd)).collect()
+    }
+
+    /// Runs `command` on the windows matching `criteria`.
+    pub fn for_window(
+        &mut self,
+        criteria: Criteria,
+        command: &str,
+    ) -> Result<reply::Command, MessageError> {
+        self.run_command(&format!("{} {}", criteria, command))
+    }
+
+    /// Swaps the containers with the ids `a` and `b`.
+    pub fn swap_containers(&mut self, a: i64, b: i64) -> Result<reply::Command, MessageError> {
+        self.run_command(&format!("[con_id={}] swap container with con_id {}", a, b))
+    }
+
+    /// Switches to the workspace with the given name.
+    pub fn focus_workspace(&mut self, name: &str) -> Result<reply::Command, MessageError> {
+        self.run_command(&format!("workspace {}", quote(name)))
+    }
+
+    /// Switches to the workspace with the given number, whatever its name.
+    pub fn focus_workspace_number(&mut self, num: i32) -> Result<reply::Command, MessageError> {
+        self.run_command(&format!("workspace number {}", num))
+    }
+
+    /// Renames the focused workspace to `new_name`. The focused workspace is looked up first so
+    /// the rename names it explicitly, rather than acting on whatever is focused by the time i3
+    /// runs the command. Returns `Ok(None)` without running anything if no workspace is
+    /// focused.
+    pub fn rename_focused_workspace(
+        &mut self,
+        new_name: &str,
+    ) -> Result<Option<reply::Command>, MessageError> {
+        let old_name = match self
+            .get_workspaces()?
+            .workspaces
+            .into_iter()
+            .find(|w| w.focused)
+        {
+            Some(workspace) => workspace.name,
+            None => return Ok(None),
+        };
+        self.run_command(&format!(
+            "rename workspace {} to {}",
+            quote(&old_name),
+            quote(new_name)
+        ))
+        .map(Some)
+    }
+
+    /// Sets `mark` on the focused container. With `toggle` the mark is removed instead if the
+    /// container already has it.
+    pub fn mark_focused(
+        &mut self,
+        mark: &str,
+        toggle: bool,
+    ) -> Result<reply::Command, MessageError> {
+        if toggle {
+            self.run_command(&format!("mark --toggle {}", quote(mark)))
+        } else {
+            self.run_command(&format!("mark {}", quote(mark)))
+        }
+    }
+
+    /// Focuses the container with the mark `mark`.
+    pub fn focus_mark(&mut self, mark: &str) -> Result<reply::Command, MessageError> {
+        self.for_window(Criteria::new().con_mark(mark), "focus")
+    }
+
+    /// Removes `mark` from whichever container has it, or every mark if `mark` is None.
+    pub fn unmark(&mut self, mark: Option<&str>) -> Result<reply::Command, MessageError> {
+        match mark {
+            Some(mark) => self.run_command(&format!("unmark {}", quote(mark))),
+            None => self.run_command("unmark"),
+        }
+    }
+
+    /// Makes the focused window fullscreen. With `global` it spans all outputs rather than
+    /// just its own.
+    pub fn set_fullscreen(&mut self, global: bool) -> Result<reply::Command, MessageError> {
+        self.run_command(if global {
+            "fullscreen enable global"
+        } else {
+            "fullscreen enable"
+        })
+    }
+
+    /// Toggles fullscreen on the focused window. With `global` it spans all outputs rather than
+    /// just its own.
+    pub fn toggle_fullscreen(&mut self, global: bool) -> Result<reply::Command, MessageError> {
+        self.run_command(if global {
+            "fullscreen toggle global"
+        } else {
+            "fullscreen toggle"
+        })
+    }
+
+    /// Restores the layout saved in the JSON file at `path` into the focused workspace, as
+    /// placeholder containers that swallow matching windows when they appear.
+    pub fn append_layout(&mut self, path: &str) -> Result<reply::Command, MessageError> {
+        self.run_command(&format!("append_layout {}", quote(path)))
+    }
+
+    /// Starts logging to a shared memory buffer of `size_bytes` bytes, which `i3-dump-log`
+    /// can read.
+    pub fn enable_shmlog(&mut self, size_bytes: u32) -> Result<reply::Command, MessageError> {
+        self.run_command(&format!("shmlog {}", size_bytes))
+    }
+
+    /// Stops logging to shared memory.
+    pub fn disable_shmlog(&mut self) -> Result<reply::Command, MessageError> {
+        self.run_command("shmlog off")
+    }
+
+    /// Turns debug logging on or off.
+    pub fn toggle_debug_logging(&mut self) -> Result<reply::Command, MessageError> {
+        self.run_command("debuglog toggle")
+    }
+
+    /// Applies `config` to the output called `name`.
+    #[cfg(feature = "sway-1-1")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway-1-1")))]
+    pub fn configure_output(
+        &mut self,
+        name: &str,
+        config: OutputConfig,
+    ) -> Result<reply::Command, MessageError> {
+        self.run_command(&config.command(name))
+    }
+
+    /// Turns the output called `name` on or off with DPMS. Pass `"*"` for every output.
+    #[cfg(feature = "sway-1-1")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway-1-1")))]
+    pub fn set_output_power(
+        &mut self,
+        name: &str,
+        on: bool,
+    ) -> Result<reply::Command, MessageError> {
+        let name = if name == "*" {
+            name.to_owned()
+        } else {
+            quote(name)
+        };
+        self.run_command(&format!(
+            "output {} dpms {}",
+            name,
+            if on { "on" } else { "off" }
+        ))
+    }
+
+    /// Gets the current workspaces.
+    pub fn get_workspaces(&mut self) -> Result<reply::Workspaces, MessageError> {
+        let j: json::Value = self.send_receive_i3_message(1, "")?;
+        let jworkspaces = expect_array(&j)?;
+        let workspaces = jworkspaces
+            .iter()
+            .map(common::build_workspace)
+            .collect::<Result<_, _>>()?;
+        Ok(reply::Workspaces { workspaces })
+    }
+
+    /// Gets the current outputs.
+    pub fn get_outputs(&mut self) -> Result<reply::Outputs, MessageError> {
+        let j: json::Value = self.send_receive_i3_message(3, "")?;
+        let joutputs = expect_array(&j)?;
+        let outputs = joutputs
+            .iter()
+            .map(common::build_output)
+            .collect::<Result<_, _>>()?;
+        Ok(reply::Outputs { outputs })
+    }
+
+    /// Gets the output that holds the focused workspace, if any.
+    pub fn get_focused_output(&mut self) -> Result<Option<reply::Output>, MessageError> {
+        let focused = match self
+            .get_workspaces()?
+            .workspaces
+            .into_iter()
+            .find(|w| w.focused)
+        {
+            Some(workspace) => workspace.name,
+            None => return Ok(None),
+        };
+        Ok(self
+            .get_outputs()?
+            .outputs
+            .into_iter()
+            .find(|o| o.current_workspace.as_ref() == Some(&focused)))
+    }
+
+    /// Gets the layout tree. i3 uses a tree as data structure which includes every container.
+    pub fn get_tree(&mut self) -> Result<reply::Node, MessageError> {
+        let val: json::Value = self.send_receive_i3_message(4, "")?;
+        Ok(common::build_tree(&val)?)
+    }
+
+    /// Gets the windows in the scratchpad: the floating containers on i3's internal
+    /// `__i3_scratch` workspace.
+    pub fn get_scratchpad(&mut self) -> Result<Vec<reply::Node>, MessageError> {
+        let tree = self.get_tree()?;
+        Ok(find_workspace(&tree, "__i3_scratch")
+            .map(|scratch| scratch.floating_nodes.clone())
+            .unwrap_or_default())
+    }
+
+    /// Gets the currently focused container, or None if nothing in the tree is focused. Saves
+    /// the caller from searching the tree themselves when only the focused container matters.
+    pub fn get_focused(&mut self) -> Result<Option<reply::Node>, MessageError> {
+        let tree = self.get_tree()?;
+        Ok(tree.focused().cloned())
+    }
+
+    /// Gets the part of the layout tree below the workspace called `name`, or None if there is
+    /// no such workspace.
+    pub fn get_workspace_tree(&mut self, name: &str) -> Result<Option<reply::Node>, MessageError> {
+        let tree = self.get_tree()?;
+        Ok(find_workspace(&tree, name).cloned())
+    }
+
+    /// Whether the workspace called `name` holds no windows, tiling or floating. Returns
+    /// `None` if there is no such workspace, which is also what i3 does with an empty
+    /// workspace once it loses focus.
+    pub fn is_workspace_empty(&mut self, name: &str) -> Result<Option<bool>, MessageError> {
+        let tree = self.get_tree()?;
+        Ok(find_workspace(&tree, name).map(|w| w.leaves().is_empty()))
+    }
+
+    /// Gets each output in the tree along with the workspaces on it, in tree order. i3's
+    /// internal `__i3` output, which holds the scratchpad, is left out.
+    pub fn get_outputs_with_workspaces(
+        &mut self,
+    ) -> Result<Vec<(reply::Node, Vec<reply::Node>)>, MessageError> {
+        let tree = self.get_tree()?;
+        Ok(tree
+            .nodes
+            .into_iter()
+            .filter(|n| n.nodetype == reply::NodeType::Output && !n.is_internal())
+            .map(|output| {
+                let mut workspaces = Vec::new();
+                collect_workspaces(&output, &mut workspaces);
+                (output, workspaces)
+            })
+            .collect())
+    }
+
+    /// Gets a list of marks (identifiers for containers to easily jump to them later).
+    pub fn get_marks(&mut self) -> Result<reply::Marks, MessageError> {
+        let marks: Vec<String> = self.send_receive_i3_message(5, "")?;
         Ok(reply::Marks { marks })
     }
 
-    /// Gets an array with all configured bar IDs.
-    pub fn get_bar_ids(&mut self) -> Result<reply::BarIds, MessageError> {
-        let ids: Vec<String> = self.stream.send_receive_i3_message(6, "")?;
-        Ok(reply::BarIds { ids })
+    /// Gets every mark in the tree paired with the id of the container carrying it, in tree
+    /// order. Unlike `get_marks` this keeps track of which container each mark belongs to.
+    pub fn get_marks_with_containers(&mut self) -> Result<Vec<(String, i64)>, MessageError> {
+        let tree = self.get_tree()?;
+        let mut marks = Vec::new();
+        collect_marks(&tree, &mut marks);
+        Ok(marks)
+    }
+
+    /// Gets an array with all configured bar IDs.
+    pub fn get_bar_ids(&mut self) -> Result<reply::BarIds, MessageError> {
+        let ids: Vec<String> = self.send_receive_i3_message(6, "")?;
+        Ok(reply::BarIds { ids })
+    }
+
+    /// Gets the configuration of the workspace bar with the given ID.
+    pub fn get_bar_config(&mut self, id: &str) -> Result<reply::BarConfig, MessageError> {
+        let j: json::Value = self.send_receive_i3_message(6, id)?;
+        // with an empty id i3 replies with the list of bar ids, and with an unknown id it
+        // replies with an error object
+        if !j.is_object() || j.get("error").is_some() {
+            return Err(unexpected_reply(&j, "a bar config"));
+        }
+        Ok(common::build_bar_config(&j)?)
+    }
+
+    /// Gets the version of i3. The reply will include the major, minor, patch and human-readable
+    /// version.
+    pub fn get_version(&mut self) -> Result<reply::Version, MessageError> {
+        let j: json::Value = self.send_receive_i3_message(7, "")?;
+        Ok(reply::Version {
+            major: j.get("major").unwrap().as_i64().unwrap() as i32,
+            minor: j.get("minor").unwrap().as_i64().unwrap() as i32,
+            patch: j.get("patch").unwrap().as_i64().unwrap() as i32,
+            human_readable: j
+                .get("human_readable")
+                .unwrap()
+                .as_str()
+                .unwrap()
+                .to_owned(),
+            loaded_config_file_name: j
+                .get("loaded_config_file_name")
+                .unwrap()
+                .as_str()
+                .unwrap()
+                .to_owned(),
+        })
+    }
+
+    /// Gets the list of currently configured binding modes.
+    #[cfg(feature = "i3-4-13")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-13")))]
+    pub fn get_binding_modes(&mut self) -> Result<reply::BindingModes, MessageError> {
+        let modes: Vec<String> = self.send_receive_i3_message(8, "")?;
+        Ok(reply::BindingModes { modes })
+    }
+
+    /// Returns the last loaded i3 config.
+    #[cfg(feature = "i3-4-14")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-14")))]
+    pub fn get_config(&mut self) -> Result<reply::Config, MessageError> {
+        let j: json::Value = self.send_receive_i3_message(9, "")?;
+        Ok(common::build_config(&j)?)
+    }
+
+    /// Bounds how long sending a request may block. A send that takes longer fails with
+    /// `MessageError::SendTimedOut`. `None` (the default) blocks indefinitely.
+    ///
+    /// Part of the request may already have been written when the timeout hits, so the
+    /// connection shouldn't be used for further requests afterwards.
+    pub fn set_write_timeout(&mut self, dur: Option<Duration>) -> io::Result<()> {
+        self.stream.set_write_timeout(dur)
+    }
+
+    /// Closes the connection. Unlike dropping the connection this reports any error that
+    /// occurs while shutting down the socket.
+    pub fn shutdown(self) -> io::Result<()> {
+        self.stream.shutdown(Shutdown::Both)
+    }
+
+    /// Gets the name of the currently active binding mode.
+    #[cfg(feature = "i3-4-19")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-19")))]
+    pub fn get_binding_state(&mut self) -> Result<reply::BindingState, MessageError> {
+        let j: json::Value = self.send_receive_i3_message(12, "")?;
+        let name = j
+            .get("name")
+            .and_then(|n| n.as_str())
+            .ok_or_else(|| unexpected_reply(&j, "a binding state"))?;
+        Ok(reply::BindingState {
+            name: name.to_owned(),
+        })
+    }
+
+    /// Gets the name of the currently active binding mode, e.g. at startup before any mode
+    /// event has been received.
+    ///
+    /// i3 can only report this from version 4.19 on, so this delegates to `get_binding_state`
+    /// when the `i3-4-19` feature is enabled. Without it there is no way to ask i3 and this
+    /// always returns `None`; track `ModeEvent`s instead.
+    pub fn current_mode(&mut self) -> Result<Option<String>, MessageError> {
+        #[cfg(feature = "i3-4-19")]
+        {
+            self.get_binding_state().map(|state| Some(state.name))
+        }
+        #[cfg(not(feature = "i3-4-19"))]
+        {
+            Ok(None)
+        }
+    }
+}
+
+/// The workspace called `name`, if `node` contains it.
+fn find_workspace<'a>(node: &'a reply::Node, name: &str) -> Option<&'a reply::Node> {
+    if node.nodetype == reply::NodeType::Workspace && node.name.as_deref() == Some(name) {
+        return Some(node);
+    }
+    node.nodes
+        .iter()
+        .filter_map(|n| find_workspace(n, name))
+        .next()
+}
+
+/// Pushes the workspaces below `node` onto `workspaces`.
+fn collect_workspaces(node: &reply::Node, workspaces: &mut Vec<reply::Node>) {
+    for child in &node.nodes {
+        if child.nodetype == reply::NodeType::Workspace {
+            workspaces.push(child.clone());
+        } else {
+            collect_workspaces(child, workspaces);
+        }
+    }
+}
+
+/// Pushes the marks of `node` and everything below it onto `marks`, each with its container id.
+fn collect_marks(node: &reply::Node, marks: &mut Vec<(String, i64)>) {
+    marks.extend(node.marks.iter().map(|mark| (mark.clone(), node.id)));
+    for child in node.nodes.iter().chain(&node.floating_nodes) {
+        collect_marks(child, marks);
+    }
+}
+
+impl AsRawFd for I3Connection {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+impl AsFd for I3Connection {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.stream.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common;
+    use event;
+    use json;
+    use payload_len;
+    use read_i3_message;
+    use reply;
+    use std::io::{self, Write};
+    use std::os::unix::io::{AsFd, AsRawFd};
+    use std::os::unix::net::UnixStream;
+    use std::str::FromStr;
+    use std::thread;
+    use std::time::Duration;
+    use subscribe_payload;
+    use write_i3_message;
+    use Criteria;
+    use EstablishError;
+    use I3Connection;
+    use I3EventListener;
+    use I3Funcs;
+    use MessageError;
+    use OwnedEventIterator;
+    use ParseError;
+    use Subscription;
+    use DEFAULT_MAGIC;
+
+    /// Sends an event down `stream` the way i3 would.
+    fn send_event(stream: &mut UnixStream, event_type: u32, payload: &str) {
+        stream
+            .send_i3_message(event_type | (1 << 31), payload)
+            .unwrap();
+    }
+
+    /// Spawns a fake i3 on one end of a socket pair. It answers each request with the next of
+    /// `replies`, then sends `events`, and finally returns the requests it received.
+    fn fake_i3(
+        replies: Vec<&'static str>,
+        events: Vec<(u32, String)>,
+    ) -> (UnixStream, thread::JoinHandle<Vec<(u32, String)>>) {
+        fake_i3_owned(replies.into_iter().map(|r| r.to_owned()).collect(), events)
+    }
+
+    /// Like `fake_i3`, for replies that are built at runtime.
+    fn fake_i3_owned(
+        replies: Vec<String>,
+        events: Vec<(u32, String)>,
+    ) -> (UnixStream, thread::JoinHandle<Vec<(u32, String)>>) {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for reply in replies {
+                let (message_type, payload) = read_i3_message(&mut server, DEFAULT_MAGIC).unwrap();
+                server.send_i3_message(message_type, &reply).unwrap();
+                requests.push((message_type, payload));
+            }
+            for (event_type, payload) in events {
+                send_event(&mut server, event_type, &payload);
+            }
+            requests
+        });
+        (client, handle)
+    }
+
+    /// A minimal container as it appears in the tree or in window events.
+    fn con_json(id: i64) -> String {
+        format!(
+            r#"{{ "id": {}, "type": "con", "border": "normal", "current_border_width": 2, "layout": "splith", "percent": null, "rect": {{ "x": 0, "y": 0, "width": 0, "height": 0 }}, "window_rect": {{ "x": 0, "y": 0, "width": 0, "height": 0 }}, "deco_rect": {{ "x": 0, "y": 0, "width": 0, "height": 0 }}, "geometry": {{ "x": 0, "y": 0, "width": 0, "height": 0 }}, "window": null, "urgent": false, "focused": false }}"#,
+            id
+        )
+    }
+
+    /// A container as it appears in the tree, with the given type, name and children.
+    fn tree_json(
+        id: i64,
+        nodetype: &str,
+        name: &str,
+        nodes: Vec<String>,
+        floating_nodes: Vec<String>,
+    ) -> String {
+        let mut con: json::Value = json::from_str(&con_json(id)).unwrap();
+        {
+            let con = con.as_object_mut().unwrap();
+            con.insert("type".to_owned(), json::Value::from(nodetype));
+            con.insert("name".to_owned(), json::Value::from(name));
+            let parse = |children: Vec<String>| {
+                children
+                    .iter()
+                    .map(|c| json::from_str(c).unwrap())
+                    .collect::<Vec<json::Value>>()
+            };
+            con.insert("nodes".to_owned(), json::Value::from(parse(nodes)));
+            con.insert(
+                "floating_nodes".to_owned(),
+                json::Value::from(parse(floating_nodes)),
+            );
+        }
+        con.to_string()
+    }
+
+    /// A minimal node with the given id, type and children.
+    fn node(id: i64, nodetype: reply::NodeType, nodes: Vec<reply::Node>) -> reply::Node {
+        let mut node = common::build_tree(&json::from_str(&con_json(id)).unwrap()).unwrap();
+        node.nodetype = nodetype;
+        node.nodes = nodes;
+        node
+    }
+
+    /// A leaf container holding an X11 window.
+    fn window(id: i64) -> reply::Node {
+        let mut node = node(id, reply::NodeType::Con, vec![]);
+        node.window = Some(id as u32 * 100);
+        node
+    }
+
+    /// A window event with the given change about a minimal container.
+    fn window_event_json(change: &str, id: i64) -> String {
+        format!(
+            r#"{{ "change": "{}", "container": {} }}"#,
+            change,
+            con_json(id)
+        )
+    }
+
+    // for the following tests send a request and get the reponse.
+    // response types are specific so often getting them at all indicates success.
+    // can't do much better without mocking an i3 installation.
+
+    #[test]
+    fn connect() {
+        I3Connection::connect().unwrap();
+    }
+
+    #[test]
+    fn run_command_nothing() {
+        let mut connection = I3Connection::connect().unwrap();
+        let result = connection.run_command("").unwrap();
+        assert_eq!(result.outcomes.len(), 0);
+    }
+
+    #[test]
+    fn run_command_single_sucess() {
+        let mut connection = I3Connection::connect().unwrap();
+        let a = connection.run_command("exec /bin/true").unwrap();
+        assert_eq!(a.outcomes.len(), 1);
+        assert!(a.outcomes[0].success);
+    }
+
+    #[test]
+    fn run_command_multiple_success() {
+        let mut connection = I3Connection::connect().unwrap();
+        let result = connection
+            .run_command("exec /bin/true; exec /bin/true")
+            .unwrap();
+        assert_eq!(result.outcomes.len(), 2);
+        assert!(result.outcomes[0].success);
+        assert!(result.outcomes[1].success);
+    }
+
+    #[test]
+    fn run_command_fail() {
+        let mut connection = I3Connection::connect().unwrap();
+        let result = connection.run_command("ThisIsClearlyNotACommand").unwrap();
+        assert_eq!(result.outcomes.len(), 1);
+        assert!(!result.outcomes[0].success);
+    }
+
+    #[test]
+    fn get_workspaces() {
+        I3Connection::connect().unwrap().get_workspaces().unwrap();
+    }
+
+    #[test]
+    fn get_outputs() {
+        I3Connection::connect().unwrap().get_outputs().unwrap();
+    }
+
+    #[test]
+    fn get_tree() {
+        I3Connection::connect().unwrap().get_tree().unwrap();
+    }
+
+    #[test]
+    fn get_marks() {
+        I3Connection::connect().unwrap().get_marks().unwrap();
+    }
+
+    #[test]
+    fn get_bar_ids() {
+        I3Connection::connect().unwrap().get_bar_ids().unwrap();
+    }
+
+    #[test]
+    fn get_bar_ids_and_one_config() {
+        let mut connection = I3Connection::connect().unwrap();
+        let ids = connection.get_bar_ids().unwrap().ids;
+        connection.get_bar_config(&ids[0]).unwrap();
+    }
+
+    #[test]
+    fn get_version() {
+        I3Connection::connect().unwrap().get_version().unwrap();
+    }
+
+    #[cfg(feature = "i3-4-13")]
+    #[test]
+    fn get_binding_modes() {
+        I3Connection::connect()
+            .unwrap()
+            .get_binding_modes()
+            .unwrap();
+    }
+
+    #[cfg(feature = "i3-4-14")]
+    #[test]
+    fn get_config() {
+        I3Connection::connect().unwrap().get_config().unwrap();
+    }
+
+    #[cfg(feature = "i3-4-19")]
+    #[test]
+    fn get_binding_state() {
+        I3Connection::connect()
+            .unwrap()
+            .get_binding_state()
+            .unwrap();
+    }
+
+    #[test]
+    fn event_subscribe() {
+        let s = I3EventListener::connect()
+            .unwrap()
+            .subscribe(&[Subscription::Workspace])
+            .unwrap();
+        assert!(s.success);
+    }
+
+    #[test]
+    fn from_str_workspace() {
+        let json_str = r##"
+        {
+            "change": "focus",
+            "current": {
+                "id": 28489712,
+                "name": "something",
+                "type": "workspace",
+                "border": "normal",
+                "current_border_width": 2,
+                "layout": "splith",
+                "orientation": "none",
+                "percent": 30.0,
+                "rect": { "x": 1600, "y": 0, "width": 1600, "height": 1200 },
+                "window_rect": { "x": 2, "y": 0, "width": 632, "height": 366 },
+                "deco_rect": { "x": 1, "y": 1, "width": 631, "height": 365 },
+                "geometry": { "x": 6, "y": 6, "width": 10, "height": 10 },
+                "window": 1,
+                "urgent": false,
+                "focused": true
+            },
+            "old": null
+        }"##;
+        event::WorkspaceEventInfo::from_str(json_str).unwrap();
+    }
+
+    #[test]
+    fn from_str_workspace_rename() {
+        let json_str = r##"
+        {
+            "change": "rename",
+            "current": {
+                "id": 28489712,
+                "name": "2: web",
+                "type": "workspace",
+                "border": "normal",
+                "current_border_width": 2,
+                "layout": "splith",
+                "percent": null,
+                "rect": { "x": 0, "y": 0, "width": 1600, "height": 1200 },
+                "window_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                "geometry": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                "window": null,
+                "urgent": false,
+                "focused": true
+            },
+            "old": {
+                "id": 28489712,
+                "name": "2",
+                "type": "workspace",
+                "border": "normal",
+                "current_border_width": 2,
+                "layout": "splith",
+                "percent": null,
+                "rect": { "x": 0, "y": 0, "width": 1600, "height": 1200 },
+                "window_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                "geometry": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                "window": null,
+                "urgent": false,
+                "focused": true
+            }
+        }"##;
+        let info = event::WorkspaceEventInfo::from_str(json_str).unwrap();
+        assert!(info.is_rename());
+        assert_eq!(info.current.unwrap().name, Some("2: web".to_owned()));
+        assert_eq!(info.old.unwrap().name, Some("2".to_owned()));
+    }
+
+    #[test]
+    fn from_str_workspace_current_is_named() {
+        let event = |name: &str, num: i32| {
+            let mut current: json::Value =
+                json::from_str(&tree_json(1, "workspace", name, vec![], vec![])).unwrap();
+            current["num"] = json::Value::from(num);
+            json::json!({ "change": "focus", "current": current, "old": null }).to_string()
+        };
+        let info = event::WorkspaceEventInfo::from_str(&event("web", -1)).unwrap();
+        assert_eq!(info.current.as_ref().unwrap().num, Some(-1));
+        assert_eq!(info.current_is_named(), Some(true));
+
+        let info = event::WorkspaceEventInfo::from_str(&event("3", 3)).unwrap();
+        assert_eq!(info.current_is_named(), Some(false));
+
+        let info = event::WorkspaceEventInfo::from_str(
+            r#"{ "change": "reload", "current": null, "old": null }"#,
+        )
+        .unwrap();
+        assert_eq!(info.current_is_named(), None);
+    }
+
+    #[test]
+    fn from_str_workspace_unknown_change() {
+        let json_str = r##"{ "change": "teleport", "current": null, "old": null }"##;
+        let info = event::WorkspaceEventInfo::from_str(json_str).unwrap();
+        assert_eq!(info.change, event::inner::WorkspaceChange::Unknown);
+        assert_eq!(info.raw_change, "teleport");
+    }
+
+    #[test]
+    fn from_str_output() {
+        let json_str = r##"{ "change": "unspecified" }"##;
+        event::OutputEventInfo::from_str(json_str).unwrap();
+    }
+
+    #[test]
+    fn from_str_output_unknown_change() {
+        let json_str = r##"{ "change": "added" }"##;
+        let info = event::OutputEventInfo::from_str(json_str).unwrap();
+        assert_eq!(info.change, event::inner::OutputChange::Unknown);
+        assert_eq!(info.raw_change, "added");
+    }
+
+    #[test]
+    fn from_str_mode() {
+        let json_str = r##"{ "change": "default" }"##;
+        event::ModeEventInfo::from_str(json_str).unwrap();
+    }
+
+    const WINDOW_EVENT: &str = r##"
+    {
+        "change": "new",
+        "container": {
+            "id": 28489712,
+            "name": "something",
+            "type": "workspace",
+            "border": "normal",
+            "current_border_width": 2,
+            "layout": "splith",
+            "orientation": "none",
+            "percent": 30.0,
+            "rect": { "x": 1600, "y": 0, "width": 1600, "height": 1200 },
+            "window_rect": { "x": 2, "y": 0, "width": 632, "height": 366 },
+            "deco_rect": { "x": 1, "y": 1, "width": 631, "height": 365 },
+            "geometry": { "x": 6, "y": 6, "width": 10, "height": 10 },
+            "window": 1,
+            "window_properties": { "class": "Firefox", "instance": "Navigator", "window_role": "browser", "title": "github.com - Mozilla Firefox", "transient_for": null },
+            "urgent": false,
+            "focused": true
+        }
+    }"##;
+
+    #[test]
+    fn from_str_window() {
+        event::WindowEventInfo::from_str(WINDOW_EVENT).unwrap();
+    }
+
+    #[test]
+    fn window_property_accessors() {
+        let container = event::WindowEventInfo::from_str(WINDOW_EVENT)
+            .unwrap()
+            .container;
+        assert_eq!(container.class(), Some("Firefox"));
+        assert_eq!(container.instance(), Some("Navigator"));
+        assert_eq!(container.window_role(), Some("browser"));
+        assert!(container.matches_class_ci("firefox"));
+        assert!(container.matches_class_ci("FIREFOX"));
+        assert!(!container.matches_class_ci("fire"));
+
+        let split = node(1, reply::NodeType::Con, vec![]);
+        assert_eq!(split.class(), None);
+        assert!(!split.matches_class_ci("firefox"));
+    }
+
+    #[test]
+    fn from_str_window_urgent() {
+        let json_str =
+            window_event_json("urgent", 1).replace(r#""urgent": false"#, r#""urgent": true"#);
+        let info = event::WindowEventInfo::from_str(&json_str).unwrap();
+        assert_eq!(info.became_urgent(), Some(true));
+
+        let info = event::WindowEventInfo::from_str(&window_event_json("urgent", 1)).unwrap();
+        assert_eq!(info.became_urgent(), Some(false));
+
+        let info = event::WindowEventInfo::from_str(&window_event_json("focus", 1)).unwrap();
+        assert_eq!(info.became_urgent(), None);
+    }
+
+    #[test]
+    fn window_event_parity() {
+        let payloads = vec![
+            WINDOW_EVENT.to_owned(),
+            window_event_json("focus", 1),
+            window_event_json("mark", 2),
+            window_event_json("made_up", 3),
+        ];
+        for payload in payloads {
+            let direct = event::WindowEventInfo::from_str(&payload).unwrap();
+            let val: json::Value = json::from_str(&payload).unwrap();
+            let via_value: event::WindowEventInfo = json::from_value(val).unwrap();
+            assert_eq!(direct.change, via_value.change);
+            assert_eq!(direct.raw_change, via_value.raw_change);
+            assert_eq!(direct.container.id, via_value.container.id);
+            assert_eq!(direct.container.name, via_value.container.name);
+            assert_eq!(direct.container.rect, via_value.container.rect);
+            assert_eq!(
+                direct.container.window_properties,
+                via_value.container.window_properties
+            );
+        }
+
+        assert!(event::WindowEventInfo::from_str(r#"{ "change": "focus" }"#).is_err());
+    }
+
+    #[test]
+    fn from_str_window_mark() {
+        let info = event::WindowEventInfo::from_str(&window_event_json("mark", 1)).unwrap();
+        #[cfg(feature = "i3-4-13")]
+        assert_eq!(info.change, event::inner::WindowChange::Mark);
+        #[cfg(not(feature = "i3-4-13"))]
+        assert_eq!(info.change, event::inner::WindowChange::Unknown);
+        assert_eq!(info.raw_change, "mark");
+    }
+
+    #[test]
+    fn from_str_window_marks() {
+        let json_str = window_event_json("mark", 1).replace(
+            r#""focused": false"#,
+            r#""focused": true, "marks": ["urgent"]"#,
+        );
+        let info = event::WindowEventInfo::from_str(&json_str).unwrap();
+        assert_eq!(info.container.marks, vec!["urgent"]);
+        assert!(info.container.focused);
+
+        let info = event::WindowEventInfo::from_str(WINDOW_EVENT).unwrap();
+        assert!(info.container.marks.is_empty());
+    }
+
+    #[test]
+    fn clone_window_event() {
+        let info = event::WindowEventInfo::from_str(WINDOW_EVENT).unwrap();
+        let event = event::Event::WindowEvent(info);
+        let copy = event.clone();
+        let sent = thread::spawn(move || copy).join().unwrap();
+        match (event, sent) {
+            (event::Event::WindowEvent(a), event::Event::WindowEvent(b)) => {
+                assert_eq!(a.change, b.change);
+                assert_eq!(a.raw_change, b.raw_change);
+                assert_eq!(a.container.id, b.container.id);
+                assert_eq!(a.container.name, b.container.name);
+                assert_eq!(a.container.window, b.container.window);
+                assert_eq!(a.container.rect, b.container.rect);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn from_str_window_transient_for() {
+        let json_str = window_event_json("new", 1).replace(
+            r#""window": null"#,
+            r#""window": 4194310, "window_properties": { "class": "Firefox", "title": "Save As", "transient_for": 4194305 }"#,
+        );
+        let info = event::WindowEventInfo::from_str(&json_str).unwrap();
+        assert_eq!(info.change, event::inner::WindowChange::New);
+        assert_eq!(info.container.transient_for, Some(4194305));
+        assert_eq!(
+            info.container.window_properties.unwrap()[&reply::WindowProperty::TransientFor],
+            "4194305"
+        );
+
+        let info = event::WindowEventInfo::from_str(&window_event_json("new", 1)).unwrap();
+        assert_eq!(info.container.transient_for, None);
+
+        let mut con: json::Value = json::from_str(&con_json(1)).unwrap();
+        con["window_properties"] = json::json!({ "transient_for": -1 });
+        assert_eq!(
+            common::build_tree(&con).unwrap_err(),
+            ParseError::InvalidField("transient_for")
+        );
+    }
+
+    #[test]
+    fn from_str_barconfig() {
+        let json_str = r##"
+        {
+            "id": "bar-bxuqzf",
+            "mode": "dock",
+            "position": "bottom",
+            "status_command": "i3status",
+            "font": "-misc-fixed-medium-r-normal--13-120-75-75-C-70-iso10646-1",
+            "workspace_buttons": true,
+            "binding_mode_indicator": true,
+            "verbose": false,
+            "colors": {
+                    "background": "#c0c0c0",
+                    "statusline": "#00ff00",
+                    "focused_workspace_text": "#ffffff",
+                    "focused_workspace_bg": "#000000"
+            }
+        }"##;
+        event::BarConfigEventInfo::from_str(json_str).unwrap();
+    }
+
+    #[test]
+    fn from_str_binding_event() {
+        let json_str = r##"
+        {
+            "change": "run",
+            "binding": {
+                "command": "nop",
+                "event_state_mask": [
+                    "shift",
+                    "ctrl"
+                ],
+                "input_code": 0,
+                "symbol": "t",
+                "input_type": "keyboard"
+            }
+        }"##;
+        let info = event::BindingEventInfo::from_str(json_str).unwrap();
+        assert_eq!(
+            info.binding.modifiers(),
+            vec![reply::ModKey::Shift, reply::ModKey::Control]
+        );
+    }
+
+    #[test]
+    fn from_str_binding_event_input_device() {
+        let json_str = r##"
+        {
+            "change": "run",
+            "binding": {
+                "command": "exec foot",
+                "event_state_mask": ["Mod4"],
+                "input_code": 0,
+                "symbols": ["Return", "KP_Enter"],
+                "input_device": "1:1:AT_Translated_Set_2_keyboard",
+                "input_type": "keyboard"
+            }
+        }"##;
+        let binding = event::BindingEventInfo::from_str(json_str).unwrap().binding;
+        assert_eq!(
+            binding.input_device.as_deref(),
+            Some("1:1:AT_Translated_Set_2_keyboard")
+        );
+        assert_eq!(binding.symbols, vec!["Return", "KP_Enter"]);
+        assert_eq!(binding.symbol.as_deref(), Some("Return"));
+
+        let json_str = json_str
+            .replace(r#""symbols": ["Return", "KP_Enter"],"#, r#""symbol": "t","#)
+            .replace(r#""input_device": "1:1:AT_Translated_Set_2_keyboard","#, "");
+        let binding = event::BindingEventInfo::from_str(&json_str)
+            .unwrap()
+            .binding;
+        assert_eq!(binding.input_device, None);
+        assert_eq!(binding.symbols, vec!["t"]);
+        assert_eq!(binding.symbol.as_deref(), Some("t"));
+    }
+
+    #[test]
+    fn from_str_binding_event_unknown_change() {
+        let json_str = r##"
+        {
+            "change": "release",
+            "binding": {
+                "command": "nop",
+                "event_state_mask": [],
+                "input_code": 0,
+                "symbol": null,
+                "input_type": "keyboard"
+            }
+        }"##;
+        let info = event::BindingEventInfo::from_str(json_str).unwrap();
+        assert_eq!(info.change, event::inner::BindingChange::Unknown);
+        assert_eq!(info.raw_change, "release");
+    }
+
+    #[cfg(feature = "i3-4-14")]
+    #[test]
+    fn from_str_shutdown_unknown_change() {
+        let info = event::ShutdownEventInfo::from_str(r##"{ "change": "crash" }"##).unwrap();
+        assert_eq!(info.change, event::inner::ShutdownChange::Unknown);
+        assert_eq!(info.raw_change, "crash");
+    }
+
+    #[test]
+    fn add_subscription_with_events_in_flight() {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let mut listener = I3EventListener::from_stream(client);
+
+        // the first event arrives in two parts, with poll_events buffering the first
+        let mut first = Vec::new();
+        write_i3_message(
+            &mut first,
+            "i3-ipc",
+            3 | (1 << 31),
+            &window_event_json("focus", 1),
+        )
+        .unwrap();
+        server.write_all(&first[..10]).unwrap();
+        assert!(listener.poll_events().is_empty());
+        server.write_all(&first[10..]).unwrap();
+        send_event(&mut server, 3, &window_event_json("close", 2));
+        server.send_i3_message(2, r#"{ "success": true }"#).unwrap();
+
+        assert!(
+            listener
+                .add_subscription(Subscription::Window)
+                .unwrap()
+                .success
+        );
+        for id in [1, 2] {
+            match listener.next_event().unwrap() {
+                event::Event::WindowEvent(w) => assert_eq!(w.container.id, id),
+                other => panic!("expected a window event, got {:?}", other),
+            }
+        }
+
+        server.send_i3_message(4, "[]").unwrap();
+        match listener.add_subscription(Subscription::Mode) {
+            Err(MessageError::UnexpectedReply(_)) => {}
+            other => panic!("expected UnexpectedReply, got {:?}", other),
+        }
+        server.send_i3_message(2, "{}").unwrap();
+        match listener.add_subscription(Subscription::Mode) {
+            Err(MessageError::UnexpectedReply(_)) => {}
+            other => panic!("expected UnexpectedReply, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn add_subscription() {
+        let (stream, fake) = fake_i3(
+            vec![r#"{ "success": true }"#, r#"{ "success": true }"#],
+            vec![
+                (
+                    0,
+                    r#"{ "change": "focus", "current": null, "old": null }"#.to_owned(),
+                ),
+                (3, window_event_json("urgent", 1)),
+            ],
+        );
+        let mut listener = I3EventListener::from_stream(stream);
+        assert!(
+            listener
+                .subscribe(&[Subscription::Workspace])
+                .unwrap()
+                .success
+        );
+        assert!(
+            listener
+                .add_subscription(Subscription::Window)
+                .unwrap()
+                .success
+        );
+        assert_eq!(
+            listener.subscriptions(),
+            &[Subscription::Workspace, Subscription::Window]
+        );
+
+        let mut events = listener.listen();
+        match events.next().unwrap().unwrap() {
+            event::Event::WorkspaceEvent(_) => {}
+            other => panic!("expected a workspace event, got {:?}", other),
+        }
+        match events.next().unwrap().unwrap() {
+            event::Event::WindowEvent(_) => {}
+            other => panic!("expected a window event, got {:?}", other),
+        }
+
+        let requests = fake.join().unwrap();
+        assert_eq!(requests[0], (2, r#"["workspace"]"#.to_owned()));
+        assert_eq!(requests[1], (2, r#"["workspace","window"]"#.to_owned()));
+    }
+
+    #[test]
+    fn color_from_hex() {
+        use reply::Color;
+        let rgb = |r, g, b, a| Some(Color { r, g, b, a });
+        assert_eq!(Color::from_hex("#fa0"), rgb(0xff, 0xaa, 0x00, None));
+        assert_eq!(Color::from_hex("#c0c0c0"), rgb(0xc0, 0xc0, 0xc0, None));
+        assert_eq!(
+            Color::from_hex("#00ff0080"),
+            rgb(0x00, 0xff, 0x00, Some(0x80))
+        );
+        assert_eq!(Color::from_hex("c0c0c0"), None);
+        assert_eq!(Color::from_hex("#c0c0c"), None);
+        assert_eq!(Color::from_hex("#gg0000"), None);
+        assert_eq!(Color::from_hex("#"), None);
+    }
+
+    #[test]
+    fn build_tree_extra() {
+        let mut con: json::Value = json::from_str(&con_json(1)).unwrap();
+        con["some_future_field"] = json::json!({ "answer": 42 });
+        let node = common::build_tree(&con).unwrap();
+        assert_eq!(
+            node.extra.get("some_future_field"),
+            Some(&json::json!({ "answer": 42 }))
+        );
+        assert!(!node.extra.contains_key("id"));
+        assert!(!node.extra.contains_key("window_properties"));
+    }
+
+    #[test]
+    fn bar_config_extra() {
+        let json_str = r##"
+        {
+            "id": "bar-bxuqzf",
+            "mode": "dock",
+            "position": "bottom",
+            "status_command": "i3status",
+            "font": "pango:monospace 8",
+            "separator_symbol": " | ",
+            "workspace_buttons": true,
+            "binding_mode_indicator": true,
+            "verbose": false,
+            "colors": {}
+        }"##;
+        let bar_config = event::BarConfigEventInfo::from_str(json_str)
+            .unwrap()
+            .bar_config;
+        assert_eq!(bar_config.extra.len(), 1);
+        assert_eq!(
+            bar_config.extra.get("separator_symbol"),
+            Some(&json::Value::from(" | "))
+        );
+    }
+
+    #[test]
+    fn bar_config_color() {
+        use reply::{Color, ColorableBarPart};
+        let json_str = r##"
+        {
+            "id": "bar-bxuqzf",
+            "mode": "dock",
+            "position": "bottom",
+            "status_command": "i3status",
+            "font": "-misc-fixed-medium-r-normal--13-120-75-75-C-70-iso10646-1",
+            "workspace_buttons": true,
+            "binding_mode_indicator": true,
+            "verbose": false,
+            "colors": {
+                    "background": "#c0c0c0",
+                    "statusline": "#00ff00",
+                    "focused_workspace_text": "not a color"
+            }
+        }"##;
+        let bar_config = event::BarConfigEventInfo::from_str(json_str)
+            .unwrap()
+            .bar_config;
+        assert_eq!(
+            bar_config.color(ColorableBarPart::Background),
+            Some(Color {
+                r: 0xc0,
+                g: 0xc0,
+                b: 0xc0,
+                a: None
+            })
+        );
+        assert_eq!(
+            bar_config.color(ColorableBarPart::FocusedWorkspaceText),
+            None
+        );
+        assert_eq!(bar_config.color(ColorableBarPart::Separator), None);
+    }
+
+    #[test]
+    fn window_events() {
+        let (stream, _fake) = fake_i3(
+            vec![],
+            vec![
+                (
+                    0,
+                    r#"{ "change": "focus", "current": null, "old": null }"#.to_owned(),
+                ),
+                (3, window_event_json("title", 1)),
+                (2, r#"{ "change": "resize" }"#.to_owned()),
+                (3, window_event_json("close", 2)),
+            ],
+        );
+        let mut listener = I3EventListener::from_stream(stream);
+        let windows: Vec<_> = listener
+            .listen()
+            .window_events()
+            .take(2)
+            .map(|w| w.unwrap())
+            .collect();
+        assert_eq!(windows[0].change, event::inner::WindowChange::Title);
+        assert_eq!(windows[0].container.id, 1);
+        assert_eq!(windows[1].change, event::inner::WindowChange::Close);
+        assert_eq!(windows[1].container.id, 2);
+    }
+
+    #[cfg(feature = "i3-4-19")]
+    #[test]
+    fn current_mode() {
+        let (stream, fake) = fake_i3(vec![r#"{ "name": "resize" }"#], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        assert_eq!(
+            connection.current_mode().unwrap(),
+            Some("resize".to_owned())
+        );
+        assert_eq!(fake.join().unwrap(), vec![(12, "".to_owned())]);
+    }
+
+    #[cfg(feature = "i3-4-19")]
+    #[test]
+    fn get_binding_state_malformed() {
+        let (stream, _fake) = fake_i3(vec!["{}", "{}"], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        match connection.get_binding_state() {
+            Err(MessageError::UnexpectedReply(_)) => {}
+            other => panic!("expected UnexpectedReply, got {:?}", other),
+        }
+        match connection.current_mode() {
+            Err(MessageError::UnexpectedReply(_)) => {}
+            other => panic!("expected UnexpectedReply, got {:?}", other),
+        }
+    }
+
+    #[cfg(not(feature = "i3-4-19"))]
+    #[test]
+    fn current_mode() {
+        let (stream, fake) = fake_i3(vec![], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        assert_eq!(connection.current_mode().unwrap(), None);
+        assert!(fake.join().unwrap().is_empty());
+    }
+
+    #[test]
+    fn shutdown() {
+        let (stream, _fake) = fake_i3(vec![], vec![]);
+        I3Connection::from_stream(stream).shutdown().unwrap();
+        let (stream, _fake) = fake_i3(vec![], vec![]);
+        I3EventListener::from_stream(stream).shutdown().unwrap();
+    }
+
+    #[test]
+    fn into_events() {
+        struct App {
+            events: OwnedEventIterator,
+        }
+
+        let (stream, _fake) = fake_i3(vec![], vec![(2, r#"{ "change": "resize" }"#.to_owned())]);
+        let mut app = App {
+            events: I3EventListener::from_stream(stream).into_events(),
+        };
+        match app.events.next().unwrap().unwrap() {
+            event::Event::ModeEvent(e) => assert_eq!(e.change, "resize"),
+            other => panic!("expected a mode event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn node_title() {
+        let container = event::WindowEventInfo::from_str(WINDOW_EVENT)
+            .unwrap()
+            .container;
+        assert_eq!(container.title(), Some("github.com - Mozilla Firefox"));
+
+        let mut split = container.clone();
+        split.window_properties = None;
+        assert_eq!(split.title(), Some("something"));
+    }
+
+    #[test]
+    fn subscribe_all() {
+        let (stream, fake) = fake_i3(vec![r#"{ "success": true }"#], vec![]);
+        let mut listener = I3EventListener::from_stream(stream);
+        assert!(listener.subscribe_all().unwrap().success);
+        assert_eq!(listener.subscriptions(), Subscription::all());
+
+        let requests = fake.join().unwrap();
+        let payload = &requests[0].1;
+        assert!(payload.contains(r#""barconfig_update""#));
+        assert_eq!(payload.contains(r#""shutdown""#), cfg!(feature = "i3-4-14"));
+    }
+
+    #[test]
+    fn subscribe_error() {
+        let (stream, _fake) = fake_i3(
+            vec![
+                r#"{ "success": false, "error": "Unknown event type \"tick\"" }"#,
+                r#"{ "success": true }"#,
+            ],
+            vec![],
+        );
+        let mut listener = I3EventListener::from_stream(stream);
+        let reply = listener.subscribe(&[Subscription::Window]).unwrap();
+        assert!(!reply.success);
+        assert_eq!(reply.error.as_deref(), Some(r#"Unknown event type "tick""#));
+
+        let reply = listener.subscribe(&[Subscription::Window]).unwrap();
+        assert!(reply.success);
+        assert_eq!(reply.error, None);
+    }
+
+    #[test]
+    fn unknown_event_type() {
+        let (stream, _fake) = fake_i3(
+            vec![],
+            vec![
+                (42, r#"{ "change": "whatever" }"#.to_owned()),
+                (2, r#"{ "change": "default" }"#.to_owned()),
+            ],
+        );
+        let mut listener = I3EventListener::from_stream(stream);
+        let mut events = listener.listen();
+        match events.next().unwrap() {
+            Err(MessageError::UnknownEventType(42)) => {}
+            other => panic!("expected an unknown event type error, got {:?}", other),
+        }
+        // the stream is still usable afterwards
+        match events.next().unwrap().unwrap() {
+            event::Event::ModeEvent(e) => assert_eq!(e.change, "default"),
+            other => panic!("expected a mode event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn next_event_deadline() {
+        use std::time::Instant;
+
+        let (stream, mut server) = UnixStream::pair().unwrap();
+        let mut listener = I3EventListener::from_stream(stream);
+        let start = Instant::now();
+        assert!(listener.next_event_deadline(start).unwrap().is_none());
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        let deadline = Instant::now() + Duration::from_millis(30);
+        assert!(listener.next_event_deadline(deadline).unwrap().is_none());
+        assert!(Instant::now() >= deadline);
+
+        send_event(&mut server, 3, &window_event_json("focus", 1));
+        send_event(&mut server, 3, &window_event_json("close", 2));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        match listener.next_event_deadline(deadline).unwrap() {
+            Some(event::Event::WindowEvent(w)) => assert_eq!(w.container.id, 1),
+            other => panic!("expected a window event, got {:?}", other),
+        }
+        // the second event may already be buffered, and listen picks it up from there
+        match listener.listen().next().unwrap().unwrap() {
+            event::Event::WindowEvent(w) => assert_eq!(w.container.id, 2),
+            other => panic!("expected a window event, got {:?}", other),
+        }
+        assert!(listener.stream.read_timeout().unwrap().is_none());
+    }
+
+    #[test]
+    fn poll_events() {
+        let (stream, fake) = fake_i3(
+            vec![],
+            vec![
+                (2, r#"{ "change": "resize" }"#.to_owned()),
+                (3, window_event_json("focus", 1)),
+                (2, r#"{ "change": "default" }"#.to_owned()),
+            ],
+        );
+        fake.join().unwrap();
+        let mut listener = I3EventListener::from_stream(stream);
+        let events = listener.poll_events();
+        // the fake i3 hung up after its last event, which is reported after the events.
+        assert_eq!(events.len(), 4);
+        match events[0] {
+            Ok(event::Event::ModeEvent(ref e)) => assert_eq!(e.change, "resize"),
+            ref other => panic!("expected a mode event, got {:?}", other),
+        }
+        match events[1] {
+            Ok(event::Event::WindowEvent(ref e)) => assert_eq!(e.container.id, 1),
+            ref other => panic!("expected a window event, got {:?}", other),
+        }
+        match events[2] {
+            Ok(event::Event::ModeEvent(ref e)) => assert_eq!(e.change, "default"),
+            ref other => panic!("expected a mode event, got {:?}", other),
+        }
+        match events[3] {
+            Err(MessageError::Receive(_)) => {}
+            ref other => panic!("expected a receive error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn poll_events_partial_message() {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let mut listener = I3EventListener::from_stream(client);
+        assert!(listener.poll_events().is_empty());
+
+        let mut bytes = Vec::new();
+        write_i3_message(
+            &mut bytes,
+            "i3-ipc",
+            2 | (1 << 31),
+            r#"{ "change": "resize" }"#,
+        )
+        .unwrap();
+        write_i3_message(
+            &mut bytes,
+            "i3-ipc",
+            2 | (1 << 31),
+            r#"{ "change": "default" }"#,
+        )
+        .unwrap();
+        let (first, second) = bytes.split_at(bytes.len() - 5);
+
+        server.write_all(first).unwrap();
+        let events = listener.poll_events();
+        assert_eq!(events.len(), 1);
+        match events[0] {
+            Ok(event::Event::ModeEvent(ref e)) => assert_eq!(e.change, "resize"),
+            ref other => panic!("expected a mode event, got {:?}", other),
+        }
+        assert!(listener.poll_events().is_empty());
+
+        // a blocking read picks up where polling left off
+        server.write_all(second).unwrap();
+        match listener.listen().next().unwrap().unwrap() {
+            event::Event::ModeEvent(e) => assert_eq!(e.change, "default"),
+            other => panic!("expected a mode event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn subscribe_payload_json() {
+        let payload = subscribe_payload(&[
+            Subscription::Workspace,
+            Subscription::BarConfig,
+            Subscription::Binding,
+        ]);
+        let parsed: Vec<String> = json::from_str(&payload).unwrap();
+        assert_eq!(parsed, vec!["workspace", "barconfig_update", "binding"]);
+        assert_eq!(subscribe_payload(&[]), "[]");
+    }
+
+    #[test]
+    fn subscription_serde() {
+        for sub in Subscription::all() {
+            let serialized = json::to_string(sub).unwrap();
+            assert_eq!(serialized, format!("\"{}\"", sub.name()));
+            assert_eq!(json::from_str::<Subscription>(&serialized).unwrap(), *sub);
+        }
+        assert_eq!(
+            json::to_string(&Subscription::BarConfig).unwrap(),
+            r#""barconfig_update""#
+        );
+        let subs: Vec<Subscription> = json::from_str(r#"["window", "barconfig_update"]"#).unwrap();
+        assert_eq!(subs, vec![Subscription::Window, Subscription::BarConfig]);
+        assert!(json::from_str::<Subscription>(r#""bar_config""#).is_err());
+    }
+
+    #[test]
+    fn raw_fd() {
+        let (stream, _fake) = fake_i3(vec![], vec![]);
+        let connection = I3Connection::from_stream(stream);
+        assert!(connection.as_raw_fd() >= 0);
+        assert_eq!(connection.as_fd().as_raw_fd(), connection.as_raw_fd());
+
+        let (stream, _fake) = fake_i3(vec![], vec![]);
+        let listener = I3EventListener::from_stream(stream);
+        assert!(listener.as_raw_fd() >= 0);
+        assert_eq!(listener.as_fd().as_raw_fd(), listener.as_raw_fd());
+    }
+
+    #[test]
+    fn bar_config_modifier() {
+        use reply::ModKey;
+        let bar_config = |modifier: &str| {
+            let json_str = format!(
+                r##"{{
+                    "id": "bar-0",
+                    "mode": "hide",
+                    "position": "bottom",
+                    "status_command": "i3status",
+                    "font": "monospace 8",
+                    "workspace_buttons": true,
+                    "binding_mode_indicator": true,
+                    "verbose": false,
+                    "modifier": "{}",
+                    "colors": {{}}
+                }}"##,
+                modifier
+            );
+            event::BarConfigEventInfo::from_str(&json_str)
+                .unwrap()
+                .bar_config
+        };
+        assert_eq!(bar_config("Mod4").modifier, vec![ModKey::Mod4]);
+        assert_eq!(
+            bar_config("ctrl+shift").modifier,
+            vec![ModKey::Control, ModKey::Shift]
+        );
+        assert_eq!(bar_config("shift+Hyper").modifier, vec![ModKey::Shift]);
+    }
+
+    #[test]
+    fn object_instead_of_array() {
+        let (stream, _fake) = fake_i3(
+            vec![
+                r#"{ "error": "not i3" }"#,
+                r#"{ "error": "not i3" }"#,
+                r#"{ "something": "else" }"#,
+            ],
+            vec![],
+        );
+        let mut connection = I3Connection::from_stream(stream);
+        match connection.run_command("nop") {
+            Err(MessageError::UnexpectedReply(ref e)) => assert!(e.contains("not i3")),
+            other => panic!("expected an unexpected reply error, got {:?}", other),
+        }
+        match connection.get_workspaces() {
+            Err(MessageError::UnexpectedReply(_)) => {}
+            other => panic!("expected an unexpected reply error, got {:?}", other),
+        }
+        match connection.get_outputs() {
+            Err(MessageError::UnexpectedReply(_)) => {}
+            other => panic!("expected an unexpected reply error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn array_of_malformed_objects() {
+        let (stream, _fake) = fake_i3(
+            vec![
+                "[{}]",
+                "[{}]",
+                r#"[{ "num": 1, "name": "1", "visible": true, "focused": true, "urgent": false, "rect": { "x": 0 }, "output": "eDP-1" }]"#,
+                r#"[{ "name": "eDP-1", "make": "", "model": "", "serial": "", "active": true, "dpms": true, "primary": true, "modes": [], "current_workspace": 1, "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 } }]"#,
+            ],
+            vec![],
+        );
+        let mut connection = I3Connection::from_stream(stream);
+        match connection.get_workspaces() {
+            Err(MessageError::UnexpectedReply(_)) => {}
+            other => panic!("expected an unexpected reply error, got {:?}", other),
+        }
+        match connection.get_outputs() {
+            Err(MessageError::UnexpectedReply(_)) => {}
+            other => panic!("expected an unexpected reply error, got {:?}", other),
+        }
+        match connection.get_workspaces() {
+            Err(MessageError::UnexpectedReply(ref e)) => assert!(e.contains("rect")),
+            other => panic!("expected an unexpected reply error, got {:?}", other),
+        }
+        match connection.get_outputs() {
+            Err(MessageError::UnexpectedReply(ref e)) => {
+                assert!(e.contains("current_workspace"))
+            }
+            other => panic!("expected an unexpected reply error, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "i3-4-20")]
+    #[test]
+    fn get_config_included_configs() {
+        let (stream, _fake) = fake_i3(
+            vec![
+                r#"{
+                    "config": "include ~/.config/i3/*.conf",
+                    "included_configs": [
+                        {
+                            "path": "/home/user/.config/i3/a.conf",
+                            "raw_contents": "set $mod Mod4",
+                            "variable_replaced_contents": "set Mod4 Mod4"
+                        },
+                        {
+                            "path": "/home/user/.config/i3/b.conf",
+                            "raw_contents": "bindsym $mod+Return exec i3-sensible-terminal",
+                            "variable_replaced_contents": "bindsym Mod4+Return exec i3-sensible-terminal"
+                        }
+                    ]
+                }"#,
+            ],
+            vec![],
+        );
+        let config = I3Connection::from_stream(stream).get_config().unwrap();
+        assert_eq!(config.config, "include ~/.config/i3/*.conf");
+        assert_eq!(config.included_configs.len(), 2);
+        assert_eq!(
+            config.included_configs[0].path,
+            "/home/user/.config/i3/a.conf"
+        );
+        assert_eq!(config.included_configs[0].raw_contents, "set $mod Mod4");
+        assert_eq!(
+            config.included_configs[1].variable_replaced_contents,
+            "bindsym Mod4+Return exec i3-sensible-terminal"
+        );
+    }
+
+    #[cfg(feature = "i3-4-20")]
+    #[test]
+    fn get_config_malformed_included_configs() {
+        let (stream, _fake) = fake_i3(
+            vec![
+                r#"{
+                    "config": "include ~/.config/i3/*.conf",
+                    "included_configs": [
+                        { "path": "/home/user/.config/i3/a.conf", "raw_contents": "" }
+                    ]
+                }"#,
+                r#"{ "config": "", "included_configs": "a.conf" }"#,
+            ],
+            vec![],
+        );
+        let mut connection = I3Connection::from_stream(stream);
+        for _ in 0..2 {
+            match connection.get_config() {
+                Err(MessageError::UnexpectedReply(ref e)) => {
+                    assert!(e.contains("included_configs"))
+                }
+                other => panic!("expected UnexpectedReply, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn swap_containers() {
+        let (stream, fake) = fake_i3(vec![r#"[{ "success": true }]"#], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        let outcomes = connection.swap_containers(94001, 94002).unwrap().outcomes;
+        assert!(outcomes[0].success);
+        assert_eq!(
+            fake.join().unwrap(),
+            vec![(
+                0,
+                "[con_id=94001] swap container with con_id 94002".to_owned()
+            )]
+        );
+    }
+
+    #[cfg(feature = "sway-1-1")]
+    #[test]
+    fn build_mode_int_and_float() {
+        let mode = common::build_mode(
+            &json::from_str(r#"{ "width": 1920, "height": 1080, "refresh": 59951 }"#).unwrap(),
+        )
+        .unwrap();
+        assert_eq!((mode.width, mode.height), (1920, 1080));
+        assert_eq!(mode.refresh, 59951);
+        assert!((mode.refresh_hz() - 59.951).abs() < 1e-9);
+
+        let mode = common::build_mode(
+            &json::from_str(r#"{ "width": 1920.0, "height": 1080.0, "refresh": 59951.4 }"#)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!((mode.width, mode.height), (1920, 1080));
+        assert_eq!(mode.refresh, 59951);
+
+        let mode = common::build_mode(
+            &json::from_str(r#"{ "width": 1920, "height": 1080, "refresh": 59.951 }"#).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(mode.refresh, 59951);
+        assert!((mode.refresh_hz() - 59.951).abs() < 1e-9);
+
+        assert_eq!(
+            common::build_mode(&json::from_str(r#"{ "width": 1920, "height": 1080 }"#).unwrap())
+                .unwrap_err(),
+            ParseError::MissingField("refresh")
+        );
+    }
+
+    #[test]
+    fn node_identity() {
+        use std::collections::HashSet;
+        let container = event::WindowEventInfo::from_str(WINDOW_EVENT)
+            .unwrap()
+            .container;
+        let mut renamed = container.clone();
+        renamed.name = Some("something else".to_owned());
+        assert_eq!(container, renamed);
+
+        let mut nodes = HashSet::new();
+        nodes.insert(container);
+        nodes.insert(renamed);
+        assert_eq!(nodes.len(), 1);
+    }
+
+    #[test]
+    fn tree_diff() {
+        use reply::NodeType::{Con, Root, Workspace};
+        use reply::{diff, TreeChange};
+        let old = node(
+            1,
+            Root,
+            vec![
+                node(
+                    2,
+                    Workspace,
+                    vec![node(3, Con, vec![]), node(4, Con, vec![])],
+                ),
+                node(5, Workspace, vec![]),
+            ],
+        );
+
+        let mut added = old.clone();
+        added.nodes[1].nodes.push(node(6, Con, vec![]));
+        assert_eq!(diff(&old, &added), vec![TreeChange::Added { id: 6 }]);
+
+        let mut removed = old.clone();
+        removed.nodes[0].nodes.remove(0);
+        assert_eq!(diff(&old, &removed), vec![TreeChange::Removed { id: 3 }]);
+
+        let mut retitled = old.clone();
+        retitled.nodes[0].nodes[1].name = Some("vim".to_owned());
+        assert_eq!(
+            diff(&old, &retitled),
+            vec![TreeChange::Retitled {
+                id: 4,
+                old: None,
+                new: Some("vim".to_owned()),
+            }]
+        );
+
+        let mut moved = old.clone();
+        let window = moved.nodes[0].nodes.remove(1);
+        moved.nodes[1].floating_nodes.push(window);
+        assert_eq!(
+            diff(&old, &moved),
+            vec![TreeChange::Moved {
+                id: 4,
+                old_parent: Some(2),
+                new_parent: Some(5),
+            }]
+        );
+
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn children_in_focus_order() {
+        use reply::NodeType::{Con, FloatingCon, Workspace};
+        let mut workspace = node(
+            1,
+            Workspace,
+            vec![
+                node(2, Con, vec![]),
+                node(3, Con, vec![]),
+                node(4, Con, vec![]),
+            ],
+        );
+        workspace.floating_nodes = vec![node(5, FloatingCon, vec![])];
+        workspace.focus = vec![3, 5, 2];
+        let ids: Vec<i64> = workspace
+            .children_in_focus_order()
+            .iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(ids, vec![3, 5, 2, 4]);
+
+        workspace.focus = vec![];
+        let ids: Vec<i64> = workspace
+            .children_in_focus_order()
+            .iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(ids, vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn floating_windows() {
+        use reply::NodeType::{Con, FloatingCon, Output, Workspace};
+        let mut first = node(2, Workspace, vec![window(3)]);
+        first.floating_nodes = vec![
+            node(4, FloatingCon, vec![window(5)]),
+            // a floating split container holding two windows
+            node(
+                6,
+                FloatingCon,
+                vec![node(7, Con, vec![window(8), window(9)])],
+            ),
+        ];
+        let mut second = node(10, Workspace, vec![window(11)]);
+        second.floating_nodes = vec![node(12, FloatingCon, vec![window(13)])];
+        let output = node(1, Output, vec![first, second]);
+
+        let ids = |nodes: Vec<&reply::Node>| nodes.iter().map(|n| n.id).collect::<Vec<i64>>();
+        assert_eq!(ids(output.floating_windows()), vec![5, 8, 9, 13]);
+        assert_eq!(ids(output.nodes[0].floating_windows()), vec![5, 8, 9]);
+        assert!(window(3).floating_windows().is_empty());
+    }
+
+    #[test]
+    fn focus_path() {
+        use reply::NodeType::{Con, FloatingCon, Output, Root, Workspace};
+        let mut tree = node(
+            1,
+            Root,
+            vec![node(
+                2,
+                Output,
+                vec![
+                    node(3, Workspace, vec![node(4, Con, vec![])]),
+                    node(
+                        5,
+                        Workspace,
+                        vec![node(
+                            6,
+                            Con,
+                            vec![node(7, Con, vec![]), node(8, Con, vec![])],
+                        )],
+                    ),
+                ],
+            )],
+        );
+        tree.focus = vec![2];
+        tree.nodes[0].focus = vec![5, 3];
+        tree.nodes[0].nodes[1].focus = vec![6];
+        tree.nodes[0].nodes[1].nodes[0].focus = vec![8, 7];
+        tree.nodes[0].nodes[1].nodes[0].nodes[1].focused = true;
+        assert_eq!(tree.focus_path(), vec![1, 2, 5, 6, 8]);
+        assert_eq!(tree.focused().unwrap().id, 8);
+
+        // floating containers are part of the focus chain too
+        let mut floating = node(9, FloatingCon, vec![]);
+        floating.focused = true;
+        tree.nodes[0].nodes[1].nodes[0].nodes[1].focused = false;
+        tree.nodes[0].nodes[1].floating_nodes.push(floating);
+        tree.nodes[0].nodes[1].focus = vec![9, 6];
+        assert_eq!(tree.focus_path(), vec![1, 2, 5, 9]);
+        assert_eq!(tree.focused().unwrap().id, 9);
+    }
+
+    #[test]
+    fn leaves() {
+        use reply::NodeType::{Con, FloatingCon, Output, Root, Workspace};
+        let mut tree = node(
+            1,
+            Root,
+            vec![node(
+                2,
+                Output,
+                vec![
+                    node(3, Workspace, vec![window(4)]),
+                    node(
+                        5,
+                        Workspace,
+                        vec![node(
+                            6,
+                            Con,
+                            vec![window(7), window(8), node(9, Con, vec![])],
+                        )],
+                    ),
+                    node(10, Workspace, vec![]),
+                ],
+            )],
+        );
+        tree.nodes[0].nodes[1]
+            .floating_nodes
+            .push(node(11, FloatingCon, vec![window(12)]));
+        let ids: Vec<i64> = tree.leaves().iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![4, 7, 8, 12]);
+    }
+
+    #[test]
+    fn node_index() {
+        use reply::NodeType::{Con, FloatingCon, Output, Root, Workspace};
+        let mut tree = node(
+            1,
+            Root,
+            vec![node(
+                2,
+                Output,
+                vec![
+                    node(3, Workspace, vec![node(4, Con, vec![])]),
+                    node(5, Workspace, vec![node(6, Con, vec![node(7, Con, vec![])])]),
+                ],
+            )],
+        );
+        tree.nodes[0].nodes[1].floating_nodes.push(node(
+            8,
+            FloatingCon,
+            vec![node(9, Con, vec![])],
+        ));
+        let index = reply::NodeIndex::from_tree(&tree);
+        assert_eq!(index.len(), 9);
+        for id in 1..10 {
+            assert_eq!(index.get(id).unwrap().id, id);
+        }
+        assert_eq!(index.get(6).unwrap().nodes[0].id, 7);
+        assert!(index.get(10).is_none());
+    }
+
+    #[test]
+    fn rect_accessors() {
+        let mut con = node(1, reply::NodeType::Con, vec![]);
+        con.rect = (1600, 20, 800, 1180);
+        assert_eq!(con.rect_x(), 1600);
+        assert_eq!(con.rect_y(), 20);
+        assert_eq!(con.rect_width(), 800);
+        assert_eq!(con.rect_height(), 1180);
+    }
+
+    #[test]
+    fn is_internal() {
+        let workspace = |name: &str| reply::Workspace {
+            num: -1,
+            name: name.to_owned(),
+            visible: false,
+            focused: false,
+            urgent: false,
+            rect: (0, 0, 0, 0),
+            output: "__i3".to_owned(),
+        };
+        assert!(workspace("__i3_scratch").is_internal());
+        assert!(!workspace("1: web").is_internal());
+
+        let mut scratch = node(1, reply::NodeType::Workspace, vec![]);
+        scratch.name = Some("__i3_scratch".to_owned());
+        assert!(scratch.is_internal());
+        let mut normal = node(2, reply::NodeType::Workspace, vec![]);
+        normal.name = Some("1: web".to_owned());
+        assert!(!normal.is_internal());
+        assert!(!node(3, reply::NodeType::Con, vec![]).is_internal());
+    }
+
+    #[test]
+    fn wait_for() {
+        let (stream, _fake) = fake_i3(
+            vec![],
+            vec![
+                (3, window_event_json("new", 1)),
+                (3, window_event_json("focus", 1)),
+                (3, window_event_json("focus", 2)),
+                (3, window_event_json("focus", 3)),
+            ],
+        );
+        let mut listener = I3EventListener::from_stream(stream);
+        let event = listener
+            .wait_for(|e| match *e {
+                event::Event::WindowEvent(ref w) => w.container.id == 2,
+                _ => false,
+            })
+            .unwrap();
+        match event {
+            event::Event::WindowEvent(w) => assert_eq!(w.container.id, 2),
+            other => panic!("expected a window event, got {:?}", other),
+        }
+        // the events after the match are still there
+        match listener.listen().next().unwrap().unwrap() {
+            event::Event::WindowEvent(w) => assert_eq!(w.container.id, 3),
+            other => panic!("expected a window event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn command_outcome_helpers() {
+        use reply::{Command, CommandOutcome};
+        use std::collections::HashMap;
+        let mixed = Command {
+            outcomes: vec![
+                CommandOutcome {
+                    success: true,
+                    error: None,
+                    input: None,
+                    extra: HashMap::new(),
+                },
+                CommandOutcome {
+                    success: false,
+                    error: Some("Expected one of these tokens: <end>".to_owned()),
+                    input: None,
+                    extra: HashMap::new(),
+                },
+                CommandOutcome {
+                    success: false,
+                    error: Some("No window matches given criteria".to_owned()),
+                    input: None,
+                    extra: HashMap::new(),
+                },
+            ],
+        };
+        assert!(!mixed.all_succeeded());
+        assert_eq!(
+            mixed.first_error(),
+            Some("Expected one of these tokens: <end>")
+        );
+
+        let succeeded = Command {
+            outcomes: vec![CommandOutcome {
+                success: true,
+                error: None,
+                input: None,
+                extra: HashMap::new(),
+            }],
+        };
+        assert!(succeeded.all_succeeded());
+        assert_eq!(succeeded.first_error(), None);
+    }
+
+    #[test]
+    fn run_command_nop() {
+        let (stream, fake) = fake_i3(
+            vec![
+                r#"[{ "success": true }]"#,
+                r#"[{ "success": true }, { "success": true }]"#,
+            ],
+            vec![],
+        );
+        let mut connection = I3Connection::from_stream(stream);
+        let result = connection.run_command("nop").unwrap();
+        assert_eq!(result.outcomes.len(), 1);
+        assert!(result.all_succeeded());
+        assert_eq!(result.outcomes[0].error, None);
+        assert!(result.outcomes[0].extra.is_empty());
+
+        let result = connection.run_command("nop some comment; nop").unwrap();
+        assert_eq!(result.outcomes.len(), 2);
+        assert!(result.all_succeeded());
+        let commands: Vec<String> = fake.join().unwrap().into_iter().map(|r| r.1).collect();
+        assert_eq!(commands, vec!["nop", "nop some comment; nop"]);
+    }
+
+    #[test]
+    fn run_command_outcome_input() {
+        let (stream, fake) = fake_i3(
+            vec![
+                r#"[{ "success": false, "parse_error": true, "error": "Expected one of these tokens: <end>", "input": "focus nowhere", "errorposition": "      ^^^^^^^" }]"#,
+            ],
+            vec![],
+        );
+        let mut connection = I3Connection::from_stream(stream);
+        let result = connection.run_command("focus nowhere").unwrap();
+        fake.join().unwrap();
+        let outcome = &result.outcomes[0];
+        assert!(!outcome.success);
+        assert_eq!(outcome.input, Some("focus nowhere".to_owned()));
+        assert_eq!(
+            outcome.extra.get("parse_error"),
+            Some(&json::Value::Bool(true))
+        );
+        assert!(outcome.extra.contains_key("errorposition"));
+        assert!(!outcome.extra.contains_key("input"));
+    }
+
+    #[test]
+    fn run_command_malformed_outcome() {
+        let (stream, _fake) = fake_i3(
+            vec![
+                r#"[{ "success": true, "input": 7 }]"#,
+                r#"["success"]"#,
+                r#"[{ "error": "no success" }]"#,
+            ],
+            vec![],
+        );
+        let mut connection = I3Connection::from_stream(stream);
+        for _ in 0..3 {
+            match connection.run_command("nop") {
+                Err(MessageError::UnexpectedReply(_)) => {}
+                other => panic!("expected UnexpectedReply, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn run_command_error() {
+        let (stream, _fake) = fake_i3(
+            vec![
+                r#"[{ "success": true }, { "success": false, "parse_error": true, "error": "Expected one of these tokens: <end>", "input": "focus nowhere", "errorposition": "      ^^^^^^^" }]"#,
+            ],
+            vec![],
+        );
+        let mut connection = I3Connection::from_stream(stream);
+        let result = connection.run_command("nop; focus nowhere").unwrap();
+        assert_eq!(result.outcomes[0].command_error(), None);
+        let error = result.outcomes[1].command_error().unwrap();
+        assert_eq!(error.message, "Expected one of these tokens: <end>");
+        assert_eq!(error.input.as_deref(), Some("focus nowhere"));
+        assert_eq!(error.errorposition.as_deref(), Some("      ^^^^^^^"));
+        let range = error.error_range().unwrap();
+        assert_eq!(&error.input.unwrap()[range], "nowhere");
+    }
+
+    #[test]
+    fn set_write_timeout() {
+        // nobody reads from the other end, so a large enough request fills the socket buffer
+        let (stream, _other) = UnixStream::pair().unwrap();
+        let mut connection = I3Connection::from_stream(stream);
+        let timeout = Some(Duration::from_millis(20));
+        connection.set_write_timeout(timeout).unwrap();
+        assert_eq!(connection.stream.write_timeout().unwrap(), timeout);
+        match connection.run_command(&"nop ".repeat(1 << 20)) {
+            Err(MessageError::SendTimedOut(_)) => {}
+            other => panic!("expected a send timeout, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn focus_workspace() {
+        let (stream, fake) = fake_i3(
+            vec![
+                r#"[{ "success": true }]"#,
+                r#"[{ "success": true }]"#,
+                r#"[{ "success": true }]"#,
+            ],
+            vec![],
+        );
+        let mut connection = I3Connection::from_stream(stream);
+        connection.focus_workspace("my workspace").unwrap();
+        connection.focus_workspace(r#"say "hi" \o/"#).unwrap();
+        connection.focus_workspace_number(3).unwrap();
+        let commands: Vec<String> = fake.join().unwrap().into_iter().map(|r| r.1).collect();
+        assert_eq!(
+            commands,
+            vec![
+                r#"workspace "my workspace""#,
+                r#"workspace "say \"hi\" \\o/""#,
+                "workspace number 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn listen_raw() {
+        let (stream, _fake) = fake_i3(vec![], vec![(3, window_event_json("title", 1))]);
+        let mut listener = I3EventListener::from_stream(stream);
+        let (event, raw) = listener.listen_raw().next().unwrap().unwrap();
+        let raw: json::Value = json::from_str(&raw).unwrap();
+        assert_eq!(raw["change"], "title");
+        match event {
+            event::Event::WindowEvent(w) => assert_eq!(w.raw_change, raw["change"]),
+            other => panic!("expected a window event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn next_event() {
+        let (stream, _fake) = fake_i3(
+            vec![],
+            vec![
+                (3, window_event_json("focus", 1)),
+                (3, window_event_json("close", 2)),
+            ],
+        );
+        let mut listener = I3EventListener::from_stream(stream);
+        match listener.next_event().unwrap() {
+            event::Event::WindowEvent(w) => assert_eq!(w.container.id, 1),
+            other => panic!("expected a window event, got {:?}", other),
+        }
+        match listener.next_event().unwrap() {
+            event::Event::WindowEvent(w) => {
+                assert_eq!(w.change, event::inner::WindowChange::Close)
+            }
+            other => panic!("expected a window event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn listen_restart_aware() {
+        let mut events = vec![(3, window_event_json("focus", 1))];
+        if cfg!(feature = "i3-4-14") {
+            events.push((6, r#"{ "change": "restart" }"#.to_owned()));
+        }
+        let (stream, fake) = fake_i3_owned(vec![], events);
+        let mut listener = I3EventListener::from_stream(stream);
+        let mut iter = listener.listen_restart_aware();
+        match iter.next() {
+            Some(Ok(event::Event::WindowEvent(_))) => {}
+            other => panic!("expected a window event, got {:?}", other),
+        }
+        // i3 closes the socket once it has sent the shutdown event.
+        fake.join().unwrap();
+        match iter.next() {
+            Some(Err(MessageError::Restarting)) => {}
+            other => panic!("expected Restarting, got {:?}", other),
+        }
+        assert!(iter.next().is_none());
+        assert!(iter.next().is_none());
+    }
+
+    #[cfg(feature = "i3-4-14")]
+    #[test]
+    fn listen_restart_aware_reset() {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let mut listener = I3EventListener::from_stream(client);
+        // closing with this still unread makes the kernel reset the connection
+        listener.stream.write_all(b"unread").unwrap();
+        send_event(&mut server, 6, r#"{ "change": "restart" }"#);
+        drop(server);
+
+        let mut iter = listener.listen_restart_aware();
+        match iter.next() {
+            Some(Err(MessageError::Restarting)) => {}
+            other => panic!("expected Restarting, got {:?}", other),
+        }
+        assert!(iter.next().is_none());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn log_events() {
+        let (stream, fake) = fake_i3(
+            vec![r#"{ "success": true }"#],
+            vec![
+                (3, window_event_json("focus", 1)),
+                (2, r#"{ "change": "resize" }"#.to_owned()),
+            ],
+        );
+        let mut listener = I3EventListener::from_stream(stream);
+        let mut out = Vec::new();
+        // ends with an error once the fake i3 hangs up
+        assert!(listener.log_events(&mut out).is_err());
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines, vec!["window focus: 1", "mode resize"]);
+        assert!(fake.join().unwrap()[0].1.contains(r#""window""#));
+
+        struct BrokenPipe;
+        impl Write for BrokenPipe {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let (stream, _fake) = fake_i3(
+            vec![r#"{ "success": true }"#],
+            vec![(2, r#"{ "change": "resize" }"#.to_owned())],
+        );
+        let mut listener = I3EventListener::from_stream(stream);
+        match listener.log_events(BrokenPipe) {
+            Err(MessageError::Write(ref e)) => assert_eq!(e.kind(), io::ErrorKind::BrokenPipe),
+            other => panic!("expected a write error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn display_event() {
+        let info = event::WindowEventInfo::from_str(WINDOW_EVENT).unwrap();
+        assert_eq!(
+            event::Event::WindowEvent(info).to_string(),
+            r#"window new: 28489712 "github.com - Mozilla Firefox""#
+        );
+    }
+
+    #[test]
+    fn connect_with_env_var() {
+        use std::env;
+        use SocketDiscovery;
+
+        // PATH stands in for a variable holding the socket path, so nothing has to be set
+        let discovery = SocketDiscovery::new()
+            .env_var("I3IPC_TEST_CONNECT_WITH_UNSET")
+            .env_var("PATH")
+            .binary("/nonexistent/i3");
+        assert_eq!(discovery.socket_path().unwrap(), env::var("PATH").unwrap());
+
+        let missing = SocketDiscovery::new()
+            .env_var("I3IPC_TEST_CONNECT_WITH_UNSET")
+            .binary("/nonexistent/i3");
+        match I3Connection::connect_with(&missing) {
+            Err(EstablishError::GetSocketPathError(_)) => {}
+            other => panic!("expected a socket path error, got {:?}", other),
+        }
+        match I3EventListener::connect_with(&missing) {
+            Err(EstablishError::GetSocketPathError(_)) => {}
+            other => panic!("expected a socket path error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn connect_timeout() {
+        use set_socket_path_override;
+        use std::{env, fs, process};
+        use SocketDiscovery;
+
+        // a connection attempt that never finishes, as when i3 is still starting up
+        let hang = || {
+            thread::sleep(Duration::from_secs(5));
+            Err(EstablishError::Timeout)
+        };
+        match I3Connection::connect_timeout_using(hang, Duration::from_millis(100)) {
+            Err(EstablishError::Timeout) => {}
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+
+        // a socket path with nobody listening fails straight away rather than timing out, and
+        // the override reaches the thread doing the connecting
+        let path = env::temp_dir().join(format!("i3ipc-test-timeout-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        set_socket_path_override(Some(path.to_str().unwrap().to_owned()));
+        let discovery = SocketDiscovery::new()
+            .env_var("I3IPC_TEST_CONNECT_TIMEOUT_UNSET")
+            .binary("/nonexistent/i3");
+        let result = I3Connection::connect_timeout_with(discovery, Duration::from_secs(5));
+        set_socket_path_override(None);
+        match result {
+            Err(EstablishError::SocketError(_)) => {}
+            other => panic!("expected a socket error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn connect_retry() {
+        use set_socket_path_override;
+        use std::os::unix::net::UnixListener;
+        use std::{env, fs, process};
+        use SocketDiscovery;
+
+        let path = env::temp_dir().join(format!("i3ipc-test-retry-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        set_socket_path_override(Some(path.to_str().unwrap().to_owned()));
+        let discovery = SocketDiscovery::new()
+            .env_var("I3IPC_TEST_CONNECT_RETRY_UNSET")
+            .binary("/nonexistent/i3");
+
+        for attempts in [0, 1] {
+            match I3Connection::connect_retry_with(&discovery, attempts, Duration::from_millis(0)) {
+                Err(EstablishError::SocketError(_)) => {}
+                other => panic!("expected a socket error, got {:?}", other),
+            }
+        }
+
+        let server_path = path.clone();
+        let server = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            let listener = UnixListener::bind(&server_path).unwrap();
+            listener.accept().unwrap();
+        });
+        I3Connection::connect_retry_with(&discovery, 100, Duration::from_millis(10)).unwrap();
+        server.join().unwrap();
+        set_socket_path_override(None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn get_scratchpad() {
+        let scratch = tree_json(
+            4,
+            "workspace",
+            "__i3_scratch",
+            vec![],
+            vec![
+                tree_json(5, "floating_con", "", vec![con_json(6)], vec![]),
+                tree_json(7, "floating_con", "", vec![con_json(8)], vec![]),
+            ],
+        );
+        let tree = tree_json(
+            1,
+            "root",
+            "root",
+            vec![
+                tree_json(
+                    2,
+                    "output",
+                    "__i3",
+                    vec![tree_json(3, "con", "content", vec![scratch], vec![])],
+                    vec![],
+                ),
+                tree_json(
+                    9,
+                    "output",
+                    "eDP-1",
+                    vec![tree_json(
+                        10,
+                        "con",
+                        "content",
+                        vec![tree_json(
+                            11,
+                            "workspace",
+                            "1",
+                            vec![],
+                            vec![tree_json(12, "floating_con", "", vec![], vec![])],
+                        )],
+                        vec![],
+                    )],
+                    vec![],
+                ),
+            ],
+            vec![],
+        );
+        let (stream, fake) = fake_i3_owned(vec![tree], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        let ids: Vec<i64> = connection
+            .get_scratchpad()
+            .unwrap()
+            .iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(ids, vec![5, 7]);
+        assert_eq!(fake.join().unwrap()[0].0, 4);
+    }
+
+    #[test]
+    fn urgent_windows() {
+        use reply::NodeType::{Con, Output, Root, Workspace};
+        let mut tree = node(
+            1,
+            Root,
+            vec![node(
+                2,
+                Output,
+                vec![
+                    node(3, Workspace, vec![window(4)]),
+                    node(5, Workspace, vec![node(6, Con, vec![window(7), window(8)])]),
+                ],
+            )],
+        );
+        assert!(!tree.has_urgent_descendant());
+        assert!(tree.urgent_windows().is_empty());
+
+        // i3 marks every parent up to the workspace as urgent as well
+        tree.nodes[0].nodes[1].urgent = true;
+        tree.nodes[0].nodes[1].nodes[0].urgent = true;
+        tree.nodes[0].nodes[1].nodes[0].nodes[1].urgent = true;
+        assert!(tree.has_urgent_descendant());
+        assert!(!tree.nodes[0].nodes[0].has_urgent_descendant());
+        let ids: Vec<i64> = tree.urgent_windows().iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![8]);
+    }
+
+    #[test]
+    fn run_command_reconnecting() {
+        use set_socket_path_override;
+        use std::os::unix::net::UnixListener;
+        use std::{env, fs, process};
+
+        let path = env::temp_dir().join(format!("i3ipc-test-reconnect-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let server = UnixListener::bind(&path).unwrap();
+        let restarted = thread::spawn(move || {
+            let (mut stream, _) = server.accept().unwrap();
+            let (message_type, payload) = read_i3_message(&mut stream, DEFAULT_MAGIC).unwrap();
+            stream
+                .send_i3_message(message_type, r#"[{ "success": true }]"#)
+                .unwrap();
+            payload
+        });
+
+        // the old i3 is gone
+        let (stream, old_i3) = UnixStream::pair().unwrap();
+        drop(old_i3);
+        let mut connection = I3Connection::from_stream(stream);
+        set_socket_path_override(Some(path.to_str().unwrap().to_owned()));
+        let result = connection.run_command_reconnecting("reload");
+        set_socket_path_override(None);
+        assert!(result.unwrap().all_succeeded());
+        assert_eq!(restarted.join().unwrap(), "reload");
+        fs::remove_file(&path).unwrap();
+
+        // other errors aren't retried
+        let (stream, fake) = fake_i3(vec![r#"{ "error": "nope" }"#], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        match connection.run_command_reconnecting("reload") {
+            Err(MessageError::UnexpectedReply(_)) => {}
+            other => panic!("expected UnexpectedReply, got {:?}", other),
+        }
+        assert_eq!(fake.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn run_command_reconnecting_with_discovery() {
+        use set_socket_path_override;
+        use std::os::unix::net::UnixListener;
+        use std::{env, fs, process};
+        use SocketDiscovery;
+
+        let path = env::temp_dir().join(format!("i3ipc-test-rediscover-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let server = UnixListener::bind(&path).unwrap();
+        set_socket_path_override(Some(path.to_str().unwrap().to_owned()));
+        let fork = thread::spawn(move || {
+            // the first connection dies with the old instance
+            drop(server.accept().unwrap());
+            let (mut stream, _) = server.accept().unwrap();
+            let (message_type, payload) = read_i3_message(&mut stream, "fork-ipc").unwrap();
+            write_i3_message(
+                &mut stream,
+                "fork-ipc",
+                message_type,
+                r#"[{ "success": true }]"#,
+            )
+            .unwrap();
+            payload
+        });
+
+        let discovery = SocketDiscovery::new()
+            .env_var("I3IPC_TEST_REDISCOVER_UNSET")
+            .binary("/nonexistent/i3");
+        let mut connection = I3Connection::connect_with(&discovery)
+            .unwrap()
+            .with_magic("fork-ipc");
+        let result = connection.run_command_reconnecting("reload");
+        assert!(result.unwrap().all_succeeded());
+        assert_eq!(fork.join().unwrap(), "reload");
+        set_socket_path_override(None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn socket_path_override() {
+        use set_socket_path_override;
+        use std::os::unix::net::UnixListener;
+        use std::{env, fs, process};
+        use SocketDiscovery;
+
+        let path = env::temp_dir().join(format!("i3ipc-test-override-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let server = UnixListener::bind(&path).unwrap();
+        set_socket_path_override(Some(path.to_str().unwrap().to_owned()));
+        let connection = I3Connection::connect().unwrap();
+        let (accepted, _) = server.accept().unwrap();
+        assert_eq!(
+            connection.stream.peer_addr().unwrap().as_pathname(),
+            Some(path.as_path())
+        );
+        drop(accepted);
+
+        // the override doesn't leak into other threads
+        let path_in_thread = thread::spawn(|| {
+            SocketDiscovery::new()
+                .env_var("I3IPC_TEST_OVERRIDE_UNSET")
+                .binary("/nonexistent/i3")
+                .socket_path()
+                .ok()
+        });
+        assert_eq!(path_in_thread.join().unwrap(), None);
+
+        set_socket_path_override(None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn build_tree_without_geometry() {
+        let mut con: json::Value = json::from_str(&con_json(1)).unwrap();
+        assert_eq!(
+            common::build_tree(&con).unwrap().geometry,
+            Some((0, 0, 0, 0))
+        );
+        con.as_object_mut().unwrap().remove("geometry");
+        con["type"] = json::Value::from("dockarea");
+        assert_eq!(common::build_tree(&con).unwrap().geometry, None);
+    }
+
+    #[cfg(feature = "sway-1-1")]
+    #[test]
+    fn build_tree_representation() {
+        let mut con: json::Value = json::from_str(&tree_json(
+            1,
+            "con",
+            "",
+            vec![con_json(2), con_json(3)],
+            vec![],
+        ))
+        .unwrap();
+        con["representation"] = json::Value::from("H[foot firefox]");
+        let tree = common::build_tree(&con).unwrap();
+        assert_eq!(tree.representation.as_deref(), Some("H[foot firefox]"));
+        assert_eq!(tree.nodes[0].representation, None);
+    }
+
+    /// Also worth running with `--no-default-features`, where the warnings for unknown values
+    /// aren't logged.
+    #[test]
+    fn build_tree_unknown_values() {
+        let mut con: json::Value = json::from_str(&con_json(1)).unwrap();
+        con["type"] = json::Value::from("portal");
+        con["border"] = json::Value::from("dashed");
+        con["layout"] = json::Value::from("zigzag");
+        let node = common::build_tree(&con).unwrap();
+        assert_eq!(node.nodetype, reply::NodeType::Unknown);
+        assert_eq!(node.border, reply::NodeBorder::Unknown);
+        assert_eq!(node.layout, reply::NodeLayout::Unknown);
+    }
+
+    #[test]
+    fn build_tree_swallows() {
+        let mut placeholder: json::Value = json::from_str(&con_json(2)).unwrap();
+        placeholder["swallows"] = json::json!([
+            { "class": "^Firefox$", "instance": "^Navigator$" },
+            { "title": "^vim", "machine": "^laptop$" }
+        ]);
+        let workspace = json::json!({
+            "id": 1, "type": "workspace", "name": "1", "border": "normal",
+            "current_border_width": 2, "layout": "splith", "percent": null,
+            "rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+            "window_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+            "window": null, "urgent": false, "focused": false,
+            "nodes": [placeholder]
+        });
+        let tree = common::build_tree(&workspace).unwrap();
+        assert!(tree.swallows.is_empty());
+        assert_eq!(
+            tree.nodes[0].swallows,
+            vec![
+                reply::Swallow {
+                    class: Some("^Firefox$".to_owned()),
+                    instance: Some("^Navigator$".to_owned()),
+                    ..Default::default()
+                },
+                reply::Swallow {
+                    title: Some("^vim".to_owned()),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn build_tree_without_percent() {
+        let mut con: json::Value = json::from_str(&con_json(1)).unwrap();
+        con["percent"] = json::Value::from(0.5);
+        assert_eq!(common::build_tree(&con).unwrap().percent, Some(0.5));
+        con.as_object_mut().unwrap().remove("percent");
+        assert_eq!(common::build_tree(&con).unwrap().percent, None);
+    }
+
+    #[test]
+    fn build_tree_without_deco_rect() {
+        let mut root: json::Value =
+            json::from_str(&tree_json(1, "root", "root", vec![con_json(2)], vec![])).unwrap();
+        root.as_object_mut().unwrap().remove("deco_rect");
+        let tree = common::build_tree(&root).unwrap();
+        assert_eq!(tree.deco_rect, None);
+        assert_eq!(tree.nodes[0].deco_rect, Some((0, 0, 0, 0)));
+    }
+
+    #[test]
+    fn read_click_events() {
+        use statusline::{read_click_events, ClickEvent};
+        let input = concat!(
+            "[\n",
+            r#"{ "name": "clock", "instance": "utc", "button": 1, "modifiers": ["Shift", "Mod4"], "x": 1900, "y": 1070, "relative_x": 12, "relative_y": 8, "width": 80, "height": 20 }"#,
+            "\n",
+            r#",{ "button": 4, "modifiers": [], "x": 10, "y": 1070, "relative_x": 10, "relative_y": 3, "width": 40, "height": 20 }"#,
+            "\n",
+            r#",{ "button": 1, "x": 10 }"#,
+            "\n",
+        );
+        let events: Vec<_> = read_click_events(input.as_bytes()).collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            *events[0].as_ref().unwrap(),
+            ClickEvent {
+                name: Some("clock".to_owned()),
+                instance: Some("utc".to_owned()),
+                button: 1,
+                x: 1900,
+                y: 1070,
+                relative_x: 12,
+                relative_y: 8,
+                width: 80,
+                height: 20,
+                modifiers: vec![reply::ModKey::Shift, reply::ModKey::Mod4],
+            }
+        );
+        let scroll = events[1].as_ref().unwrap();
+        assert_eq!(scroll.button, 4);
+        assert_eq!(scroll.name, None);
+        assert!(scroll.modifiers.is_empty());
+        assert!(events[2].is_err());
+    }
+
+    #[test]
+    fn workspace_of() {
+        use reply::NodeType::{Con, FloatingCon, Output, Root, Workspace};
+        let mut tree = node(
+            1,
+            Root,
+            vec![node(
+                2,
+                Output,
+                vec![
+                    node(3, Workspace, vec![window(4)]),
+                    node(
+                        5,
+                        Workspace,
+                        vec![node(6, Con, vec![node(7, Con, vec![window(8)])])],
+                    ),
+                ],
+            )],
+        );
+        tree.nodes[0].nodes[0]
+            .floating_nodes
+            .push(node(9, FloatingCon, vec![window(10)]));
+        assert_eq!(tree.workspace_of(8).unwrap().id, 5);
+        assert_eq!(tree.workspace_of(4).unwrap().id, 3);
+        assert_eq!(tree.workspace_of(10).unwrap().id, 3);
+        assert_eq!(tree.workspace_of(5).unwrap().id, 5);
+        assert!(tree.workspace_of(2).is_none());
+        assert!(tree.workspace_of(11).is_none());
     }
 
-    /// Gets the configuration of the workspace bar with the given ID.
-    pub fn get_bar_config(&mut self, id: &str) -> Result<reply::BarConfig, MessageError> {
-        let ids: json::Value = self.stream.send_receive_i3_message(6, id)?;
-        Ok(common::build_bar_config(&ids))
+    #[test]
+    fn run_commands() {
+        let (stream, fake) = fake_i3(
+            vec![
+                r#"[{ "success": true }]"#,
+                r#"[{ "success": false, "error": "No such mark" }]"#,
+            ],
+            vec![],
+        );
+        let mut connection = I3Connection::from_stream(stream);
+        let replies = connection
+            .run_commands(&["workspace 1", "[con_mark=x] focus"])
+            .unwrap();
+        assert_eq!(replies.len(), 2);
+        assert!(replies[0].all_succeeded());
+        assert_eq!(replies[1].first_error(), Some("No such mark"));
+        let commands: Vec<String> = fake.join().unwrap().into_iter().map(|r| r.1).collect();
+        assert_eq!(commands, vec!["workspace 1", "[con_mark=x] focus"]);
     }
 
-    /// Gets the version of i3. The reply will include the major, minor, patch and human-readable
-    /// version.
-    pub fn get_version(&mut self) -> Result<reply::Version, MessageError> {
-        let j: json::Value = self.stream.send_receive_i3_message(7, "")?;
-        Ok(reply::Version {
-            major: j.get("major").unwrap().as_i64().unwrap() as i32,
-            minor: j.get("minor").unwrap().as_i64().unwrap() as i32,
-            patch: j.get("patch").unwrap().as_i64().unwrap() as i32,
-            human_readable: j
-                .get("human_readable")
-                .unwrap()
-                .as_str()
-                .unwrap()
-                .to_owned(),
-            loaded_config_file_name: j
-                .get("loaded_config_file_name")
-                .unwrap()
-                .as_str()
-                .unwrap()
-                .to_owned(),
-        })
+    #[test]
+    fn node_from_value() {
+        use ParseError;
+        let saved = r##"
+        {
+            "id": 94000000000000,
+            "type": "root",
+            "name": "root",
+            "border": "normal",
+            "current_border_width": -1,
+            "layout": "splith",
+            "percent": null,
+            "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 },
+            "window_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+            "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+            "geometry": { "x": 0, "y": 0, "width": 0, "height": 0 },
+            "window": null,
+            "urgent": false,
+            "focused": false,
+            "focus": [94000000000001],
+            "floating_nodes": [],
+            "nodes": [
+                {
+                    "id": 94000000000001,
+                    "type": "con",
+                    "name": "vim",
+                    "border": "pixel",
+                    "current_border_width": 2,
+                    "layout": "splith",
+                    "percent": 1.0,
+                    "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 },
+                    "window_rect": { "x": 2, "y": 0, "width": 1916, "height": 1078 },
+                    "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+                    "geometry": { "x": 0, "y": 0, "width": 800, "height": 600 },
+                    "window": 41943043,
+                    "window_properties": { "class": "XTerm", "instance": "xterm", "title": "vim" },
+                    "urgent": false,
+                    "marks": ["editor"],
+                    "focused": true,
+                    "focus": [],
+                    "floating_nodes": [],
+                    "nodes": []
+                }
+            ]
+        }"##;
+        let val: json::Value = json::from_str(saved).unwrap();
+        let tree = reply::Node::from_value(&val).unwrap();
+        assert_eq!(tree.nodetype, reply::NodeType::Root);
+        let window = tree.focused().unwrap();
+        assert_eq!(window.id, 94000000000001);
+        assert_eq!(window.window, Some(41943043));
+        assert_eq!(window.percent, Some(1.0));
+        assert_eq!(window.marks, vec!["editor"]);
+        assert_eq!(window.title(), Some("vim"));
+
+        let mut broken = val.clone();
+        broken["nodes"][0].as_object_mut().unwrap().remove("id");
+        assert_eq!(
+            reply::Node::from_value(&broken).unwrap_err(),
+            ParseError::MissingField("id")
+        );
+        let mut broken = val.clone();
+        broken["nodes"][0]["rect"] = json::Value::from("wide");
+        assert_eq!(
+            reply::Node::from_value(&broken).unwrap_err(),
+            ParseError::InvalidField("rect")
+        );
     }
 
-    /// Gets the list of currently configured binding modes.
-    #[cfg(feature = "i3-4-13")]
-    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-13")))]
-    pub fn get_binding_modes(&mut self) -> Result<reply::BindingModes, MessageError> {
-        let modes: Vec<String> = self.stream.send_receive_i3_message(8, "")?;
-        Ok(reply::BindingModes { modes })
+    #[test]
+    fn get_bar_config_invalid_id() {
+        let (stream, fake) = fake_i3(
+            vec![
+                r#"["bar-0", "bar-1"]"#,
+                r#"{ "error": "No such bar" }"#,
+                r#"{ "id": "bar-0" }"#,
+            ],
+            vec![],
+        );
+        let mut connection = I3Connection::from_stream(stream);
+        match connection.get_bar_config("") {
+            Err(MessageError::UnexpectedReply(_)) => {}
+            other => panic!("expected an unexpected reply error, got {:?}", other),
+        }
+        match connection.get_bar_config("nope") {
+            Err(MessageError::UnexpectedReply(ref e)) => assert!(e.contains("No such bar")),
+            other => panic!("expected an unexpected reply error, got {:?}", other),
+        }
+        match connection.get_bar_config("bar-0") {
+            Err(MessageError::UnexpectedReply(ref e)) => assert!(e.contains("mode")),
+            other => panic!("expected an unexpected reply error, got {:?}", other),
+        }
+        let ids: Vec<String> = fake.join().unwrap().into_iter().map(|r| r.1).collect();
+        assert_eq!(ids, vec!["", "nope", "bar-0"]);
     }
 
-    /// Returns the last loaded i3 config.
-    #[cfg(feature = "i3-4-14")]
-    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-14")))]
-    pub fn get_config(&mut self) -> Result<reply::Config, MessageError> {
-        let j: json::Value = self.stream.send_receive_i3_message(9, "")?;
-        let cfg = j.get("config").unwrap().as_str().unwrap();
-        Ok(reply::Config {
-            config: cfg.to_owned(),
-        })
+    #[test]
+    fn workspace_state() {
+        use event::WorkspaceState;
+        let workspace = |name: &str, focused, visible, output: &str, x| reply::Workspace {
+            num: name.parse().unwrap_or(-1),
+            name: name.to_owned(),
+            visible,
+            focused,
+            urgent: false,
+            rect: (x, 0, 1920, 1080),
+            output: output.to_owned(),
+        };
+        let mut state = WorkspaceState::from_snapshot(reply::Workspaces {
+            workspaces: vec![
+                workspace("1", true, true, "eDP-1", 0),
+                workspace("2", false, false, "eDP-1", 0),
+                workspace("3", false, true, "HDMI-1", 1920),
+            ],
+        });
+        let event = |change: &str, id, name: &str, edit: &dyn Fn(&mut json::Value)| {
+            let mut current: json::Value =
+                json::from_str(&tree_json(id, "workspace", name, vec![], vec![])).unwrap();
+            current["rect"] =
+                json::from_str(r#"{ "x": 0, "y": 0, "width": 1920, "height": 1080 }"#).unwrap();
+            edit(&mut current);
+            let payload = json::json!({ "change": change, "current": current, "old": null });
+            event::WorkspaceEventInfo::from_str(&payload.to_string()).unwrap()
+        };
+        let unchanged = |_: &mut json::Value| {};
+
+        state.apply(&event("init", 10, "4: mail", &unchanged));
+        state.apply(&event("focus", 10, "4: mail", &unchanged));
+        state.apply(&event("rename", 10, "4: inbox", &unchanged));
+        state.apply(&event("urgent", 11, "2", &|c| {
+            c["urgent"] = json::Value::from(true)
+        }));
+        state.apply(&event("empty", 12, "1", &unchanged));
+        state.apply(&event("move", 11, "2", &|c| {
+            c["rect"]["x"] = json::Value::from(1920)
+        }));
+
+        let summary: Vec<_> = state
+            .workspaces()
+            .iter()
+            .map(|w| {
+                (
+                    w.num,
+                    w.name.as_str(),
+                    w.focused,
+                    w.visible,
+                    w.urgent,
+                    w.output.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (2, "2", false, false, true, "HDMI-1"),
+                (3, "3", false, true, false, "HDMI-1"),
+                (4, "4: inbox", true, true, false, "eDP-1"),
+            ]
+        );
     }
-}
 
-#[cfg(test)]
-mod test {
-    use event;
-    use std::str::FromStr;
-    use I3Connection;
-    use I3EventListener;
-    use Subscription;
+    #[test]
+    fn workspace_state_rename_from_snapshot() {
+        use event::WorkspaceState;
+        let workspace = |name: &str, focused, x| reply::Workspace {
+            num: name.parse().unwrap_or(-1),
+            name: name.to_owned(),
+            visible: focused,
+            focused,
+            urgent: false,
+            rect: (x, 0, 1920, 1080),
+            output: "eDP-1".to_owned(),
+        };
+        let mut state = WorkspaceState::from_snapshot(reply::Workspaces {
+            workspaces: vec![
+                workspace("1", true, 0),
+                workspace("2", false, 0),
+                workspace("3", false, 1920),
+            ],
+        });
+        let event = |change: &str, id, name: &str, x, old: Option<(i64, &str)>| {
+            let mut current: json::Value =
+                json::from_str(&tree_json(id, "workspace", name, vec![], vec![])).unwrap();
+            current["rect"] = json::json!({ "x": x, "y": 0, "width": 1920, "height": 1080 });
+            let old = old.map(|(id, name)| {
+                json::from_str::<json::Value>(&tree_json(id, "workspace", name, vec![], vec![]))
+                    .unwrap()
+            });
+            let payload = json::json!({ "change": change, "current": current, "old": old });
+            event::WorkspaceEventInfo::from_str(&payload.to_string()).unwrap()
+        };
 
-    // for the following tests send a request and get the reponse.
-    // response types are specific so often getting them at all indicates success.
-    // can't do much better without mocking an i3 installation.
+        // i3 doesn't send `old`, so this goes by the rect, preferring the focused workspace
+        state.apply(&event("rename", 10, "1: mail", 0, None));
+        // the only workspace with this rect
+        state.apply(&event("rename", 12, "3: web", 1920, None));
+        // with `old` the name is known for sure
+        state.apply(&event("rename", 11, "2: chat", 0, Some((11, "2"))));
+        // later events find the workspaces under their new names
+        state.apply(&event("focus", 12, "3: web", 1920, None));
+        state.apply(&event("empty", 10, "1: mail", 0, None));
+
+        let summary: Vec<_> = state
+            .workspaces()
+            .iter()
+            .map(|w| (w.num, w.name.as_str(), w.focused))
+            .collect();
+        assert_eq!(summary, vec![(2, "2: chat", false), (3, "3: web", true)]);
+    }
 
     #[test]
-    fn connect() {
-        I3Connection::connect().unwrap();
+    fn get_focused_output() {
+        let (stream, fake) = fake_i3(
+            vec![
+                r#"[
+                    { "num": 1, "name": "1", "visible": true, "focused": false, "urgent": false, "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 }, "output": "eDP-1" },
+                    { "num": 2, "name": "2: web", "visible": true, "focused": true, "urgent": false, "rect": { "x": 1920, "y": 0, "width": 2560, "height": 1440 }, "output": "HDMI-1" }
+                ]"#,
+                r#"[
+                    { "name": "eDP-1", "make": "", "model": "", "serial": "", "active": true, "dpms": true, "primary": true, "modes": [], "current_workspace": "1", "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 } },
+                    { "name": "HDMI-1", "make": "", "model": "", "serial": "", "active": true, "dpms": true, "primary": false, "modes": [], "current_workspace": "2: web", "rect": { "x": 1920, "y": 0, "width": 2560, "height": 1440 } },
+                    { "name": "xroot-0", "make": "", "model": "", "serial": "", "active": false, "dpms": false, "primary": false, "modes": [], "current_workspace": null, "rect": { "x": 0, "y": 0, "width": 4480, "height": 1440 } }
+                ]"#,
+            ],
+            vec![],
+        );
+        let mut connection = I3Connection::from_stream(stream);
+        let output = connection.get_focused_output().unwrap().unwrap();
+        assert_eq!(output.name, "HDMI-1");
+        assert_eq!(output.current_workspace, Some("2: web".to_owned()));
+        let requests: Vec<u32> = fake.join().unwrap().into_iter().map(|r| r.0).collect();
+        assert_eq!(requests, vec![1, 3]);
     }
 
     #[test]
-    fn run_command_nothing() {
-        let mut connection = I3Connection::connect().unwrap();
-        let result = connection.run_command("").unwrap();
-        assert_eq!(result.outcomes.len(), 0);
+    fn output_at() {
+        let (stream, _fake) = fake_i3(
+            vec![
+                r#"[
+                    { "name": "xroot-0", "make": "", "model": "", "serial": "", "active": false, "dpms": false, "primary": false, "modes": [], "current_workspace": null, "rect": { "x": 0, "y": 0, "width": 4480, "height": 1440 } },
+                    { "name": "eDP-1", "make": "", "model": "", "serial": "", "active": true, "dpms": true, "primary": true, "modes": [], "current_workspace": "1", "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 } },
+                    { "name": "HDMI-1", "make": "", "model": "", "serial": "", "active": true, "dpms": true, "primary": false, "modes": [], "current_workspace": "2", "rect": { "x": 1920, "y": 0, "width": 2560, "height": 1440 } }
+                ]"#,
+            ],
+            vec![],
+        );
+        let outputs = I3Connection::from_stream(stream).get_outputs().unwrap();
+        assert!(outputs.outputs[1].contains_point(0, 0));
+        assert!(outputs.outputs[1].contains_point(1919, 1079));
+        assert!(!outputs.outputs[1].contains_point(1920, 0));
+        assert!(!outputs.outputs[1].contains_point(-1, 10));
+
+        let name_at = |x, y| outputs.output_at(x, y).map(|o| o.name.as_str());
+        assert_eq!(name_at(100, 100), Some("eDP-1"));
+        assert_eq!(name_at(1920, 0), Some("HDMI-1"));
+        assert_eq!(name_at(4479, 1439), Some("HDMI-1"));
+        // below eDP-1, where only the inactive xroot-0 reaches
+        assert_eq!(name_at(100, 1200), None);
+        assert_eq!(name_at(4480, 0), None);
     }
 
+    #[cfg(feature = "sway-1-1")]
     #[test]
-    fn run_command_single_sucess() {
-        let mut connection = I3Connection::connect().unwrap();
-        let a = connection.run_command("exec /bin/true").unwrap();
-        assert_eq!(a.outcomes.len(), 1);
-        assert!(a.outcomes[0].success);
+    fn output_transform_and_subpixel() {
+        use reply::{Subpixel, Transform};
+        let (stream, fake) = fake_i3(
+            vec![
+                r#"[
+                    { "name": "eDP-1", "make": "", "model": "", "serial": "", "active": true, "dpms": true, "primary": false, "modes": [], "current_workspace": "1", "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 }, "transform": "90", "subpixel_hinting": "rgb" },
+                    { "name": "DP-1", "make": "", "model": "", "serial": "", "active": true, "dpms": true, "primary": false, "modes": [], "current_workspace": "2", "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 }, "transform": "flipped-270", "subpixel_hinting": "unknown" },
+                    { "name": "DP-2", "make": "", "model": "", "serial": "", "active": false, "dpms": false, "primary": false, "modes": [], "current_workspace": null, "rect": { "x": 0, "y": 0, "width": 0, "height": 0 } }
+                ]"#,
+            ],
+            vec![],
+        );
+        let outputs = I3Connection::from_stream(stream)
+            .get_outputs()
+            .unwrap()
+            .outputs;
+        fake.join().unwrap();
+        assert_eq!(outputs[0].transform_kind(), Some(Transform::Rotate90));
+        assert_eq!(outputs[0].subpixel_kind(), Some(Subpixel::Rgb));
+        assert_eq!(outputs[1].transform_kind(), Some(Transform::Flipped270));
+        assert_eq!(outputs[1].subpixel_kind(), Some(Subpixel::Unknown));
+        assert_eq!(outputs[2].transform_kind(), None);
+        assert_eq!(outputs[2].subpixel_kind(), None);
+
+        assert_eq!(common::build_transform("rotate_90"), Transform::Rotate90);
+        assert_eq!(common::build_transform("normal"), Transform::Normal);
+        assert_eq!(common::build_transform("sideways"), Transform::Unknown);
+        assert_eq!(common::build_subpixel("diagonal"), Subpixel::Unknown);
     }
 
     #[test]
-    fn run_command_multiple_success() {
-        let mut connection = I3Connection::connect().unwrap();
-        let result = connection
-            .run_command("exec /bin/true; exec /bin/true")
+    fn invalid_magic() {
+        let (stream, mut server) = UnixStream::pair().unwrap();
+        let fake = thread::spawn(move || {
+            read_i3_message(&mut server, DEFAULT_MAGIC).unwrap();
+            server
+                .write_all(b"\0AUTH \x00\x00\x00\x00\x00\x00\x00\x00")
+                .unwrap();
+        });
+        let mut connection = I3Connection::from_stream(stream);
+        match connection.get_marks() {
+            Err(MessageError::InvalidMagic { ref got }) => assert_eq!(got, "\0AUTH "),
+            other => panic!("expected an invalid magic error, got {:?}", other),
+        }
+        fake.join().unwrap();
+
+        let (stream, mut server) = UnixStream::pair().unwrap();
+        server
+            .write_all(b"i3-IPC\x02\x00\x00\x00\x00\x00\x00\x80{}")
             .unwrap();
-        assert_eq!(result.outcomes.len(), 2);
-        assert!(result.outcomes[0].success);
-        assert!(result.outcomes[1].success);
+        let mut listener = I3EventListener::from_stream(stream);
+        match listener.listen().next() {
+            Some(Err(MessageError::InvalidMagic { ref got })) => assert_eq!(got, "i3-IPC"),
+            other => panic!("expected an invalid magic error, got {:?}", other),
+        }
     }
 
     #[test]
-    fn run_command_fail() {
-        let mut connection = I3Connection::connect().unwrap();
-        let result = connection.run_command("ThisIsClearlyNotACommand").unwrap();
-        assert_eq!(result.outcomes.len(), 1);
-        assert!(!result.outcomes[0].success);
+    fn node_layout_helpers() {
+        use reply::NodeLayout::*;
+        let expected = [
+            (SplitH, false, true),
+            (SplitV, false, true),
+            (Stacked, true, false),
+            (Tabbed, true, false),
+            (DockArea, false, false),
+            (Output, false, false),
+            (Unknown, false, false),
+        ];
+        for &(ref layout, stacking, split) in &expected {
+            assert_eq!(layout.is_stacking(), stacking, "{:?}", layout);
+            assert_eq!(layout.is_split(), split, "{:?}", layout);
+        }
     }
 
     #[test]
-    fn get_workspaces() {
-        I3Connection::connect().unwrap().get_workspaces().unwrap();
+    fn custom_magic() {
+        let (stream, mut server) = UnixStream::pair().unwrap();
+        let fake = thread::spawn(move || {
+            let request = read_i3_message(&mut server, "fork-ipc").unwrap();
+            write_i3_message(&mut server, "fork-ipc", 5, r#"["a", "b"]"#).unwrap();
+            write_i3_message(
+                &mut server,
+                "fork-ipc",
+                2 | (1 << 31),
+                r#"{ "change": "x" }"#,
+            )
+            .unwrap();
+            request
+        });
+        let mut connection = I3Connection::from_stream(stream).with_magic("fork-ipc");
+        assert_eq!(connection.get_marks().unwrap().marks, vec!["a", "b"]);
+        assert_eq!(fake.join().unwrap(), (5, String::new()));
+
+        let mut listener = I3EventListener::from_stream(connection.stream).with_magic("fork-ipc");
+        match listener.listen().next() {
+            Some(Ok(event::Event::ModeEvent(info))) => assert_eq!(info.change, "x"),
+            other => panic!("expected a mode event, got {:?}", other),
+        }
     }
 
     #[test]
-    fn get_outputs() {
-        I3Connection::connect().unwrap().get_outputs().unwrap();
+    fn build_tree_large_window_id() {
+        let mut con: json::Value = json::from_str(&con_json(1)).unwrap();
+        con["window"] = json::Value::from(3_221_225_473_u64);
+        assert_eq!(
+            common::build_tree(&con).unwrap().window,
+            Some(3_221_225_473)
+        );
+        con["window"] = json::Value::from(-1);
+        assert!(common::build_tree(&con).is_err());
     }
 
     #[test]
-    fn get_tree() {
-        I3Connection::connect().unwrap().get_tree().unwrap();
+    fn reply_parsed_from_bytes() {
+        let (stream, mut server) = UnixStream::pair().unwrap();
+        let fake = thread::spawn(move || {
+            read_i3_message(&mut server, DEFAULT_MAGIC).unwrap();
+            server.send_i3_message(5, r#"["ok", "ünïcode"]"#).unwrap();
+            read_i3_message(&mut server, DEFAULT_MAGIC).unwrap();
+            let mut bytes = Vec::new();
+            write_i3_message(&mut bytes, "i3-ipc", 5, r#"["__"]"#).unwrap();
+            // replace the underscores with a byte sequence that isn't UTF-8
+            let len = bytes.len();
+            bytes[len - 4] = 0xc3;
+            bytes[len - 3] = 0x28;
+            server.write_all(&bytes).unwrap();
+        });
+        let mut connection = I3Connection::from_stream(stream);
+        assert_eq!(connection.get_marks().unwrap().marks, vec!["ok", "ünïcode"]);
+        match connection.get_marks() {
+            Err(MessageError::JsonCouldntParse(_)) => {}
+            other => panic!("expected a JSON error, got {:?}", other),
+        }
+        fake.join().unwrap();
     }
 
     #[test]
-    fn get_marks() {
-        I3Connection::connect().unwrap().get_marks().unwrap();
+    fn fullscreen() {
+        let (stream, fake) = fake_i3(vec![r#"[{ "success": true }]"#; 4], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        connection.set_fullscreen(false).unwrap();
+        connection.set_fullscreen(true).unwrap();
+        connection.toggle_fullscreen(false).unwrap();
+        connection.toggle_fullscreen(true).unwrap();
+        let commands: Vec<String> = fake.join().unwrap().into_iter().map(|r| r.1).collect();
+        assert_eq!(
+            commands,
+            vec![
+                "fullscreen enable",
+                "fullscreen enable global",
+                "fullscreen toggle",
+                "fullscreen toggle global",
+            ]
+        );
     }
 
     #[test]
-    fn get_bar_ids() {
-        I3Connection::connect().unwrap().get_bar_ids().unwrap();
+    fn for_window() {
+        let (stream, fake) = fake_i3(vec![r#"[{ "success": true }]"#; 2], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        connection
+            .for_window(Criteria::new().class("mpv"), "floating enable")
+            .unwrap();
+        let criteria = Criteria::new().title(r#"say "hi""#).con_id(7);
+        connection.for_window(criteria, "kill").unwrap();
+        let commands: Vec<String> = fake.join().unwrap().into_iter().map(|r| r.1).collect();
+        assert_eq!(
+            commands,
+            vec![
+                r#"[class="mpv"] floating enable"#,
+                r#"[title="say \"hi\"" con_id=7] kill"#,
+            ]
+        );
     }
 
     #[test]
-    fn get_bar_ids_and_one_config() {
-        let mut connection = I3Connection::connect().unwrap();
-        let ids = connection.get_bar_ids().unwrap().ids;
-        connection.get_bar_config(&ids[0]).unwrap();
+    fn rename_focused_workspace() {
+        let (stream, fake) = fake_i3(
+            vec![
+                r#"[
+                    { "num": 1, "name": "1", "visible": false, "focused": false, "urgent": false, "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 }, "output": "eDP-1" },
+                    { "num": 2, "name": "2: web stuff", "visible": true, "focused": true, "urgent": false, "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 }, "output": "eDP-1" }
+                ]"#,
+                r#"[{ "success": true }]"#,
+                r#"[]"#,
+            ],
+            vec![],
+        );
+        let mut connection = I3Connection::from_stream(stream);
+        assert!(connection
+            .rename_focused_workspace("2: \"mail\"")
+            .unwrap()
+            .unwrap()
+            .all_succeeded());
+        assert!(connection.rename_focused_workspace("3").unwrap().is_none());
+        let requests = fake.join().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(
+            requests[1].1,
+            r#"rename workspace "2: web stuff" to "2: \"mail\"""#
+        );
     }
 
     #[test]
-    fn get_version() {
-        I3Connection::connect().unwrap().get_version().unwrap();
+    fn marks() {
+        let (stream, fake) = fake_i3(vec![r#"[{ "success": true }]"#; 4], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        connection.mark_focused("my mark", false).unwrap();
+        connection
+            .mark_focused(r#"a "quoted" \ mark"#, true)
+            .unwrap();
+        connection.unmark(Some("my mark")).unwrap();
+        connection.unmark(None).unwrap();
+        let commands: Vec<String> = fake.join().unwrap().into_iter().map(|r| r.1).collect();
+        assert_eq!(
+            commands,
+            vec![
+                r#"mark "my mark""#,
+                r#"mark --toggle "a \"quoted\" \\ mark""#,
+                r#"unmark "my mark""#,
+                "unmark",
+            ]
+        );
     }
 
-    #[cfg(feature = "i3-4-13")]
     #[test]
-    fn get_binding_modes() {
-        I3Connection::connect()
-            .unwrap()
-            .get_binding_modes()
+    fn focus_mark() {
+        let (stream, fake) = fake_i3(vec![r#"[{ "success": true }]"#; 2], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        connection.focus_mark("editor").unwrap();
+        connection.focus_mark(r#"say "hi""#).unwrap();
+        let commands: Vec<String> = fake.join().unwrap().into_iter().map(|r| r.1).collect();
+        assert_eq!(
+            commands,
+            vec![
+                r#"[con_mark="editor"] focus"#,
+                r#"[con_mark="say \"hi\""] focus"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn append_layout() {
+        let (stream, fake) = fake_i3(vec![r#"[{ "success": true }]"#], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        connection
+            .append_layout("/home/me/i3 layouts/workspace 1.json")
             .unwrap();
+        let commands: Vec<String> = fake.join().unwrap().into_iter().map(|r| r.1).collect();
+        assert_eq!(
+            commands,
+            vec![r#"append_layout "/home/me/i3 layouts/workspace 1.json""#]
+        );
     }
 
-    #[cfg(feature = "i3-4-14")]
     #[test]
-    fn get_config() {
-        I3Connection::connect().unwrap().get_config().unwrap();
+    fn shmlog() {
+        let (stream, fake) = fake_i3(vec![r#"[{ "success": true }]"#; 3], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        connection.enable_shmlog(25 * 1024 * 1024).unwrap();
+        connection.disable_shmlog().unwrap();
+        connection.toggle_debug_logging().unwrap();
+        let commands: Vec<String> = fake.join().unwrap().into_iter().map(|r| r.1).collect();
+        assert_eq!(
+            commands,
+            vec!["shmlog 26214400", "shmlog off", "debuglog toggle"]
+        );
     }
 
+    #[cfg(feature = "sway-1-1")]
     #[test]
-    fn event_subscribe() {
-        let s = I3EventListener::connect()
-            .unwrap()
-            .subscribe(&[Subscription::Workspace])
+    fn set_output_power() {
+        let (stream, fake) = fake_i3(vec![r#"[{ "success": true }]"#; 3], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        connection.set_output_power("HDMI-A-1", false).unwrap();
+        connection.set_output_power("HDMI-A-1", true).unwrap();
+        connection.set_output_power("*", false).unwrap();
+        let commands: Vec<String> = fake.join().unwrap().into_iter().map(|r| r.1).collect();
+        assert_eq!(
+            commands,
+            vec![
+                r#"output "HDMI-A-1" dpms off"#,
+                r#"output "HDMI-A-1" dpms on"#,
+                "output * dpms off",
+            ]
+        );
+    }
+
+    #[cfg(feature = "sway-1-1")]
+    #[test]
+    fn configure_output() {
+        use reply::Transform;
+        use OutputConfig;
+        let (stream, fake) = fake_i3(vec![r#"[{ "success": true }]"#; 4], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        connection
+            .configure_output("HDMI-A-1", OutputConfig::new().disable())
+            .unwrap();
+        connection
+            .configure_output("HDMI-A-1", OutputConfig::new().enable())
             .unwrap();
-        assert_eq!(s.success, true);
+        let config = OutputConfig::new()
+            .mode(2560, 1440, Some(59.951))
+            .position(1920, 0)
+            .scale(1.5)
+            .transform(Transform::Rotate90);
+        connection.configure_output("DP-1", config).unwrap();
+        connection
+            .configure_output("eDP-1", OutputConfig::new().mode(1920, 1080, None))
+            .unwrap();
+        let commands: Vec<String> = fake.join().unwrap().into_iter().map(|r| r.1).collect();
+        assert_eq!(
+            commands,
+            vec![
+                r#"output "HDMI-A-1" disable"#,
+                r#"output "HDMI-A-1" enable"#,
+                r#"output "DP-1" mode 2560x1440@59.951Hz position 1920 0 scale 1.5 transform 90"#,
+                r#"output "eDP-1" mode 1920x1080"#,
+            ]
+        );
     }
 
     #[test]
-    fn from_str_workspace() {
-        let json_str = r##"
-        {
-            "change": "focus",
-            "current": {
-                "id": 28489712,
-                "name": "something",
-                "type": "workspace",
-                "border": "normal",
-                "current_border_width": 2,
-                "layout": "splith",
-                "orientation": "none",
-                "percent": 30.0,
-                "rect": { "x": 1600, "y": 0, "width": 1600, "height": 1200 },
-                "window_rect": { "x": 2, "y": 0, "width": 632, "height": 366 },
-                "deco_rect": { "x": 1, "y": 1, "width": 631, "height": 365 },
-                "geometry": { "x": 6, "y": 6, "width": 10, "height": 10 },
-                "window": 1,
-                "urgent": false,
-                "focused": true
-            },
-            "old": null
-        }"##;
-        event::WorkspaceEventInfo::from_str(json_str).unwrap();
+    fn get_workspace_tree() {
+        let content = |id, workspaces| tree_json(id, "con", "content", workspaces, vec![]);
+        let tree = tree_json(
+            1,
+            "root",
+            "root",
+            vec![
+                tree_json(
+                    2,
+                    "output",
+                    "eDP-1",
+                    vec![content(
+                        3,
+                        vec![
+                            tree_json(4, "workspace", "1", vec![con_json(5)], vec![]),
+                            tree_json(
+                                6,
+                                "workspace",
+                                "2: web",
+                                vec![con_json(7), con_json(8)],
+                                vec![],
+                            ),
+                        ],
+                    )],
+                    vec![],
+                ),
+                tree_json(
+                    9,
+                    "output",
+                    "HDMI-A-1",
+                    vec![content(
+                        10,
+                        vec![tree_json(11, "workspace", "3", vec![], vec![])],
+                    )],
+                    vec![],
+                ),
+            ],
+            vec![],
+        );
+        let (stream, _fake) = fake_i3_owned(vec![tree.clone(), tree], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        let workspace = connection.get_workspace_tree("2: web").unwrap().unwrap();
+        assert_eq!(workspace.id, 6);
+        let children: Vec<i64> = workspace.nodes.iter().map(|n| n.id).collect();
+        assert_eq!(children, vec![7, 8]);
+        assert!(connection.get_workspace_tree("4").unwrap().is_none());
     }
 
     #[test]
-    fn from_str_output() {
-        let json_str = r##"{ "change": "unspecified" }"##;
-        event::OutputEventInfo::from_str(json_str).unwrap();
+    fn get_focused() {
+        let mut focused: json::Value = json::from_str(&con_json(3)).unwrap();
+        focused["focused"] = json::Value::from(true);
+        let tree = tree_json(
+            1,
+            "workspace",
+            "1",
+            vec![con_json(2), focused.to_string()],
+            vec![],
+        );
+        let unfocused = tree_json(1, "workspace", "1", vec![con_json(2)], vec![]);
+        let (stream, _fake) = fake_i3_owned(vec![tree, unfocused], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        let node = connection.get_focused().unwrap().unwrap();
+        assert!(node.focused);
+        assert_eq!(node.id, 3);
+        assert!(connection.get_focused().unwrap().is_none());
     }
 
     #[test]
-    fn from_str_mode() {
-        let json_str = r##"{ "change": "default" }"##;
-        event::ModeEventInfo::from_str(json_str).unwrap();
+    fn is_workspace_empty() {
+        let mut window: json::Value = json::from_str(&con_json(5)).unwrap();
+        window["window"] = json::Value::from(4194310);
+        let tree = tree_json(
+            1,
+            "root",
+            "root",
+            vec![tree_json(
+                2,
+                "output",
+                "eDP-1",
+                vec![tree_json(
+                    3,
+                    "con",
+                    "content",
+                    vec![
+                        tree_json(4, "workspace", "1", vec![window.to_string()], vec![]),
+                        // a split container without windows doesn't count
+                        tree_json(6, "workspace", "2", vec![con_json(7)], vec![]),
+                    ],
+                    vec![],
+                )],
+                vec![],
+            )],
+            vec![],
+        );
+        let (stream, _fake) = fake_i3_owned(vec![tree; 3], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        assert_eq!(connection.is_workspace_empty("1").unwrap(), Some(false));
+        assert_eq!(connection.is_workspace_empty("2").unwrap(), Some(true));
+        assert_eq!(connection.is_workspace_empty("3").unwrap(), None);
     }
 
     #[test]
-    fn from_str_window() {
-        let json_str = r##"
-        {
-            "change": "new",
-            "container": {
-                "id": 28489712,
-                "name": "something",
-                "type": "workspace",
-                "border": "normal",
-                "current_border_width": 2,
-                "layout": "splith",
-                "orientation": "none",
-                "percent": 30.0,
-                "rect": { "x": 1600, "y": 0, "width": 1600, "height": 1200 },
-                "window_rect": { "x": 2, "y": 0, "width": 632, "height": 366 },
-                "deco_rect": { "x": 1, "y": 1, "width": 631, "height": 365 },
-                "geometry": { "x": 6, "y": 6, "width": 10, "height": 10 },
-                "window": 1,
-                "window_properties": { "class": "Firefox", "instance": "Navigator", "window_role": "browser", "title": "github.com - Mozilla Firefox", "transient_for": null },
-                "urgent": false,
-                "focused": true
-            }
-        }"##;
-        event::WindowEventInfo::from_str(json_str).unwrap();
+    fn get_marks_with_containers() {
+        let marked = |id, mark: &str| {
+            let mut con: json::Value = json::from_str(&con_json(id)).unwrap();
+            con["marks"] = json::json!([mark]);
+            con.to_string()
+        };
+        let tree = tree_json(
+            1,
+            "workspace",
+            "1",
+            vec![marked(2, "editor"), con_json(3)],
+            vec![marked(4, "scratch")],
+        );
+        let (stream, _fake) = fake_i3_owned(vec![tree], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        assert_eq!(
+            connection.get_marks_with_containers().unwrap(),
+            vec![("editor".to_owned(), 2), ("scratch".to_owned(), 4)]
+        );
     }
 
     #[test]
-    fn from_str_barconfig() {
-        let json_str = r##"
-        {
-            "id": "bar-bxuqzf",
-            "mode": "dock",
-            "position": "bottom",
-            "status_command": "i3status",
-            "font": "-misc-fixed-medium-r-normal--13-120-75-75-C-70-iso10646-1",
-            "workspace_buttons": true,
-            "binding_mode_indicator": true,
-            "verbose": false,
-            "colors": {
-                    "background": "#c0c0c0",
-                    "statusline": "#00ff00",
-                    "focused_workspace_text": "#ffffff",
-                    "focused_workspace_bg": "#000000"
-            }
-        }"##;
-        event::BarConfigEventInfo::from_str(json_str).unwrap();
+    fn get_outputs_with_workspaces() {
+        let output = |id, name: &str, workspaces: Vec<String>| {
+            tree_json(
+                id,
+                "output",
+                name,
+                vec![tree_json(id + 1, "con", "content", workspaces, vec![])],
+                vec![],
+            )
+        };
+        let workspace =
+            |id, name: &str| tree_json(id, "workspace", name, vec![con_json(id + 1)], vec![]);
+        let tree = tree_json(
+            1,
+            "root",
+            "root",
+            vec![
+                output(
+                    2,
+                    "__i3",
+                    vec![tree_json(4, "workspace", "__i3_scratch", vec![], vec![])],
+                ),
+                output(10, "eDP-1", vec![workspace(12, "1"), workspace(14, "2")]),
+                output(20, "HDMI-A-1", vec![workspace(22, "3")]),
+            ],
+            vec![],
+        );
+        let (stream, _fake) = fake_i3_owned(vec![tree], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+        let outputs: Vec<(String, Vec<String>)> = connection
+            .get_outputs_with_workspaces()
+            .unwrap()
+            .into_iter()
+            .map(|(output, workspaces)| {
+                (
+                    output.name.unwrap(),
+                    workspaces.into_iter().map(|w| w.name.unwrap()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            outputs,
+            vec![
+                ("eDP-1".to_owned(), vec!["1".to_owned(), "2".to_owned()]),
+                ("HDMI-A-1".to_owned(), vec!["3".to_owned()]),
+            ]
+        );
     }
 
     #[test]
-    fn from_str_binding_event() {
-        let json_str = r##"
-        {
-            "change": "run",
-            "binding": {
-                "command": "nop",
-                "event_state_mask": [
-                    "shift",
-                    "ctrl"
-                ],
-                "input_code": 0,
-                "symbol": "t",
-                "input_type": "keyboard"
+    fn version_as_tuple() {
+        let (stream, _fake) = fake_i3(
+            vec![
+                r#"{ "major": 4, "minor": 22, "patch": 1, "human_readable": "4.22.1", "loaded_config_file_name": "/etc/i3/config" }"#,
+            ],
+            vec![],
+        );
+        let version = I3Connection::from_stream(stream).get_version().unwrap();
+        assert_eq!(
+            version.as_tuple(),
+            (version.major, version.minor, version.patch)
+        );
+        assert_eq!(version.as_tuple(), (4, 22, 1));
+        assert!(version.as_tuple() >= (4, 19, 0));
+    }
+
+    #[test]
+    fn strict_utf8() {
+        use set_strict_utf8;
+        let mut message = Vec::new();
+        message.extend_from_slice(b"i3-ipc");
+        // a window event
+        message.extend_from_slice(&[14, 0, 0, 0, 3, 0, 0, 0x80]);
+        message.extend_from_slice(b"{\"title\": \"\xff\"}");
+
+        let (_, payload) = read_i3_message(&mut &message[..], "i3-ipc").unwrap();
+        assert_eq!(payload, "{\"title\": \"\u{fffd}\"}");
+
+        let (client, mut server) = UnixStream::pair().unwrap();
+        server.write_all(&message).unwrap();
+        let mut listener = I3EventListener::from_stream(client);
+        set_strict_utf8(true);
+        let strict = listener.next_event();
+        set_strict_utf8(false);
+        match strict {
+            Err(MessageError::InvalidUtf8(e)) => assert_eq!(e.valid_up_to(), 11),
+            other => panic!("expected InvalidUtf8, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn strict_parsing() {
+        use set_strict_parsing;
+        let mut con: json::Value = json::from_str(&con_json(1)).unwrap();
+        con["layout"] = json::Value::from("zigzag");
+        let tree = con.to_string();
+        let (stream, _fake) = fake_i3_owned(vec![tree.clone(), tree], vec![]);
+        let mut connection = I3Connection::from_stream(stream);
+
+        assert_eq!(
+            connection.get_tree().unwrap().layout,
+            reply::NodeLayout::Unknown
+        );
+
+        let (stream, _fake) = fake_i3_owned(
+            vec![],
+            vec![
+                (3, window_event_json("teleport", 1)),
+                (3, window_event_json("focus", 1).replace("splith", "zigzag")),
+            ],
+        );
+        let mut listener = I3EventListener::from_stream(stream);
+
+        set_strict_parsing(true);
+        let strict = connection.get_tree();
+        let change_event = listener.next_event();
+        let layout_event = listener.next_event();
+        let from_str = event::WindowEventInfo::from_str(&window_event_json("teleport", 1));
+        set_strict_parsing(false);
+        for (result, expected_field, expected_value) in [
+            (strict.map(|_| ()), "layout", "zigzag"),
+            (change_event.map(|_| ()), "change", "teleport"),
+            (layout_event.map(|_| ()), "layout", "zigzag"),
+        ] {
+            match result {
+                Err(MessageError::UnknownEnumValue { field, ref value }) => {
+                    assert_eq!(field, expected_field);
+                    assert_eq!(value, expected_value);
+                }
+                other => panic!("expected UnknownEnumValue, got {:?}", other),
             }
-        }"##;
-        event::BindingEventInfo::from_str(json_str).unwrap();
+        }
+        assert!(from_str.unwrap_err().to_string().contains("teleport"));
+    }
+
+    #[test]
+    fn payload_len_checked() {
+        assert_eq!(payload_len(0).unwrap(), 0);
+        assert_eq!(payload_len(u32::MAX as usize).unwrap(), u32::MAX);
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(
+            payload_len(u32::MAX as usize + 1).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
     }
 }
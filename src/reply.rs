@@ -1,6 +1,10 @@
 //! Abstractions for the replies passed back from i3.
 
+use common;
+use serde_json as json;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use ParseError;
 
 /// The outcome of a single command.
 #[derive(Debug)]
@@ -9,17 +13,85 @@ pub struct CommandOutcome {
     pub success: bool,
     /// A human-readable error message.
     pub error: Option<String>,
+    /// The command as i3 parsed it, if i3 echoes it back (it does for parse errors).
+    pub input: Option<String>,
+    /// Any other fields i3 included in the outcome, such as `parse_error` and `errorposition`.
+    pub extra: HashMap<String, json::Value>,
+}
+
+impl CommandOutcome {
+    /// The details of why the command failed, or None if it succeeded.
+    pub fn command_error(&self) -> Option<CommandError> {
+        if self.success {
+            return None;
+        }
+        Some(CommandError {
+            message: self.error.clone().unwrap_or_default(),
+            input: self.input.clone(),
+            errorposition: self
+                .extra
+                .get("errorposition")
+                .and_then(|p| p.as_str())
+                .map(|p| p.to_owned()),
+        })
+    }
+}
+
+/// Why a command failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandError {
+    /// The human-readable error message, empty if i3 didn't send one.
+    pub message: String,
+    /// The command as i3 parsed it. i3 sends it for parse errors.
+    pub input: Option<String>,
+    /// A marker as long as `input` that underlines the part of it that couldn't be parsed with
+    /// `^` characters, such as `"      ^^^^^^^"`. i3 sends it for parse errors.
+    pub errorposition: Option<String>,
+}
+
+impl CommandError {
+    /// The byte range of `input` that `errorposition` underlines, if there is one.
+    pub fn error_range(&self) -> Option<::std::ops::Range<usize>> {
+        let position = self.errorposition.as_ref()?;
+        let start = position.find('^')?;
+        let end = position.rfind('^')? + 1;
+        Some(start..end)
+    }
 }
 
 /// The reply to the `command` request.
 #[derive(Debug)]
 pub struct Command {
     /// A list of `CommandOutcome` structs; one for each command that was parsed.
+    ///
+    /// i3 stops at the first command it fails to parse, so there can be fewer outcomes than
+    /// commands separated by `;` or `,` in the request. An empty request has no outcomes.
+    ///
+    /// A `nop` command gets a successful outcome like any other command. i3 doesn't mark it in
+    /// any way, so there is no telling from the reply that the command had no effect.
     pub outcomes: Vec<CommandOutcome>,
 }
 
+impl Command {
+    /// Whether every parsed command was successful. Note that this is also true when there
+    /// are no outcomes at all.
+    pub fn all_succeeded(&self) -> bool {
+        self.outcomes.iter().all(|o| o.success)
+    }
+
+    /// The error message of the first command that failed, if any.
+    pub fn first_error(&self) -> Option<&str> {
+        self.outcomes
+            .iter()
+            .filter(|o| !o.success)
+            .filter_map(|o| o.error.as_ref())
+            .map(|e| e.as_str())
+            .next()
+    }
+}
+
 /// A single workspace.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Workspace {
     /// The logical number of the workspace. Corresponds to the command to switch to this
     /// workspace. For named workspaces, this will be -1.
@@ -41,6 +113,19 @@ pub struct Workspace {
     pub output: String,
 }
 
+impl Workspace {
+    /// Whether this is one of i3's internal workspaces, such as `__i3_scratch` which holds the
+    /// scratchpad.
+    pub fn is_internal(&self) -> bool {
+        is_internal_name(&self.name)
+    }
+}
+
+/// i3 prefixes the names of the outputs and workspaces it uses internally with `__i3`.
+fn is_internal_name(name: &str) -> bool {
+    name.starts_with("__i3")
+}
+
 /// The reply to the `get_workspaces` request.
 #[derive(Debug)]
 pub struct Workspaces {
@@ -54,19 +139,62 @@ pub struct Subscribe {
     /// Indicates whether the subscription was successful (the default) or whether a JSON
     /// parse error occurred.
     pub success: bool,
+    /// Why the subscription failed, if i3 said.
+    pub error: Option<String>,
 }
 
 #[cfg(feature = "sway-1-1")]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 /// A mode for sway
 pub struct Mode {
     pub width: i32,
     pub height: i32,
+    /// The refresh rate in mHz, e.g. 59951. Sway reports it in mHz; a value below 1000 is
+    /// taken to be in Hz and converted.
     pub refresh: i32,
 }
 
+#[cfg(feature = "sway-1-1")]
+impl Mode {
+    /// The refresh rate in Hz, e.g. 59.951.
+    pub fn refresh_hz(&self) -> f64 {
+        f64::from(self.refresh) / 1000.0
+    }
+}
+
+/// How an output's contents are rotated and flipped.
+#[cfg(feature = "sway-1-1")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway-1-1")))]
+#[derive(Eq, PartialEq, Debug, Hash, Clone, Copy)]
+pub enum Transform {
+    Normal,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    Flipped,
+    Flipped90,
+    Flipped180,
+    Flipped270,
+    /// A Transform we don't support yet.
+    Unknown,
+}
+
+/// The subpixel layout of an output.
+#[cfg(feature = "sway-1-1")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "sway-1-1")))]
+#[derive(Eq, PartialEq, Debug, Hash, Clone, Copy)]
+pub enum Subpixel {
+    Rgb,
+    Bgr,
+    Vrgb,
+    Vbgr,
+    None,
+    /// Sway doesn't know the layout, or it's a Subpixel we don't support yet.
+    Unknown,
+}
+
 /// A single output (display)
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Output {
     /// The name of this output (as seen in xrandr).
     pub name: String,
@@ -109,6 +237,35 @@ pub struct Output {
     pub rect: (i32, i32, i32, i32),
 }
 
+impl Output {
+    /// Whether this is i3's internal `__i3` output, which holds the scratchpad workspace.
+    pub fn is_internal(&self) -> bool {
+        is_internal_name(&self.name)
+    }
+
+    /// Whether the point `x`, `y` in absolute display coordinates lies within `rect`.
+    pub fn contains_point(&self, x: i32, y: i32) -> bool {
+        let (rx, ry, width, height) = self.rect;
+        x >= rx && x < rx + width && y >= ry && y < ry + height
+    }
+
+    /// `transform` parsed into a `Transform`.
+    #[cfg(feature = "sway-1-1")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway-1-1")))]
+    pub fn transform_kind(&self) -> Option<Transform> {
+        self.transform.as_ref().map(|t| common::build_transform(t))
+    }
+
+    /// `subpixel_hinting` parsed into a `Subpixel`.
+    #[cfg(feature = "sway-1-1")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway-1-1")))]
+    pub fn subpixel_kind(&self) -> Option<Subpixel> {
+        self.subpixel_hinting
+            .as_ref()
+            .map(|s| common::build_subpixel(s))
+    }
+}
+
 /// The reply to the `get_outputs` request.
 #[derive(Debug)]
 pub struct Outputs {
@@ -116,6 +273,17 @@ pub struct Outputs {
     pub outputs: Vec<Output>,
 }
 
+impl Outputs {
+    /// The active output whose `rect` contains the point `x`, `y` in absolute display
+    /// coordinates, such as the position of the pointer. Inactive outputs are skipped since
+    /// their rects don't describe anything on screen.
+    pub fn output_at(&self, x: i32, y: i32) -> Option<&Output> {
+        self.outputs
+            .iter()
+            .find(|o| o.active && o.contains_point(x, y))
+    }
+}
+
 #[derive(Eq, PartialEq, Debug, Hash, Clone)]
 pub enum WindowProperty {
     Title,
@@ -158,7 +326,23 @@ pub enum NodeLayout {
     Unknown,
 }
 
+impl NodeLayout {
+    /// Whether children are shown one at a time with a tab or title for each: `Tabbed` or
+    /// `Stacked`.
+    pub fn is_stacking(&self) -> bool {
+        *self == NodeLayout::Tabbed || *self == NodeLayout::Stacked
+    }
+
+    /// Whether children are shown side by side: `SplitH` or `SplitV`.
+    pub fn is_split(&self) -> bool {
+        *self == NodeLayout::SplitH || *self == NodeLayout::SplitV
+    }
+}
+
 /// The reply to the `get_tree` request.
+///
+/// Nodes compare equal and hash by their `id` alone, so two snapshots of the same container
+/// are considered the same node even if their other fields differ.
 #[derive(Debug, Clone)]
 pub struct Node {
     /// List of child node IDs (see `nodes`, `floating_nodes` and `id`) in focus order. Traversing
@@ -184,6 +368,10 @@ pub struct Node {
     /// defined (yet).
     pub name: Option<String>,
 
+    /// The number of a workspace node, or -1 if its name doesn't start with a number. None
+    /// for other containers.
+    pub num: Option<i32>,
+
     /// Type of this container. Can be one of "root", "output", "con", "floating_con",
     /// "workspace" or "dockarea".
     pub nodetype: NodeType,
@@ -219,21 +407,39 @@ pub struct Node {
 
     /// The (x, y, width, height) coordinates of the window decoration inside its container.
     /// These coordinates are relative to the container and do not include the actual client
-    /// window.
-    pub deco_rect: (i32, i32, i32, i32),
+    /// window. None if i3 didn't include it, as happens for the root and output nodes in some
+    /// versions.
+    pub deco_rect: Option<(i32, i32, i32, i32)>,
 
     /// The original geometry the window specified when i3 mapped it. Used when switching a
-    /// window to floating mode, for example.
-    pub geometry: (i32, i32, i32, i32),
+    /// window to floating mode, for example. None if i3 didn't include it, as happens for some
+    /// internal containers such as dockareas.
+    pub geometry: Option<(i32, i32, i32, i32)>,
 
     /// The X11 window ID of the actual client window inside this container. This field is set
     /// to null for split containers or otherwise empty containers. This ID corresponds to what
     /// xwininfo(1) and other X11-related tools display (usually in hex).
-    pub window: Option<i32>,
+    pub window: Option<u32>,
+
+    /// The Wayland app id of the client inside this container. Null for Xwayland windows and
+    /// containers without a client.
+    #[cfg(feature = "sway-1-1")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway-1-1")))]
+    pub app_id: Option<String>,
+
+    /// A textual description of the layout of a split container and its children, such as
+    /// `H[foot firefox]`. None for windows.
+    #[cfg(feature = "sway-1-1")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "sway-1-1")))]
+    pub representation: Option<String>,
 
     /// X11 window properties title, instance, class, window_role and transient_for.
     pub window_properties: Option<HashMap<WindowProperty, String>>,
 
+    /// The X11 window ID of the window this one is a dialog or other transient window for, from
+    /// the `transient_for` window property. None if there is none.
+    pub transient_for: Option<u32>,
+
     /// Whether this container (window, split container, floating container or workspace) has the
     /// urgency hint set, directly or indirectly. All parent containers up until the workspace
     /// container will be marked urgent if they have at least one urgent child.
@@ -241,6 +447,377 @@ pub struct Node {
 
     /// Whether this container is currently focused.
     pub focused: bool,
+
+    /// The marks set on this container. Empty if there are none, or if i3 is too old to report
+    /// them.
+    pub marks: Vec<String>,
+
+    /// The criteria for the windows a placeholder container restored with `append_layout` will
+    /// swallow. Empty for other containers.
+    pub swallows: Vec<Swallow>,
+
+    /// Any other fields i3 sent for this container, such as `sticky` or `fullscreen_mode`,
+    /// keyed by name. Lets you read fields this library doesn't model yet.
+    pub extra: HashMap<String, json::Value>,
+}
+
+/// One of the criteria a placeholder container uses to pick the window it swallows. The values
+/// are regular expressions; criteria that aren't set match anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Swallow {
+    /// The window's X11 class.
+    pub class: Option<String>,
+    /// The window's X11 instance.
+    pub instance: Option<String>,
+    /// The window's title.
+    pub title: Option<String>,
+    /// The window's X11 window role.
+    pub window_role: Option<String>,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Node {}
+
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl Node {
+    /// Parses a container, and everything below it, from the JSON i3 uses for the tree. Useful
+    /// for trees that were saved earlier.
+    pub fn from_value(v: &json::Value) -> Result<Node, ParseError> {
+        common::build_tree(v)
+    }
+
+    /// The best title available for this container: the title of its X11 window if it has
+    /// one, otherwise its `name`.
+    pub fn title(&self) -> Option<&str> {
+        self.window_properties
+            .as_ref()
+            .and_then(|props| props.get(&WindowProperty::Title))
+            .or(self.name.as_ref())
+            .map(|title| title.as_str())
+    }
+
+    /// The X11 class of this container's window, from `window_properties`.
+    pub fn class(&self) -> Option<&str> {
+        self.window_property(&WindowProperty::Class)
+    }
+
+    /// The X11 instance of this container's window, from `window_properties`.
+    pub fn instance(&self) -> Option<&str> {
+        self.window_property(&WindowProperty::Instance)
+    }
+
+    /// The X11 window role of this container's window, from `window_properties`.
+    pub fn window_role(&self) -> Option<&str> {
+        self.window_property(&WindowProperty::WindowRole)
+    }
+
+    /// Whether this container's window has the class `needle`, ignoring case.
+    pub fn matches_class_ci(&self, needle: &str) -> bool {
+        self.class()
+            .is_some_and(|class| class.to_lowercase() == needle.to_lowercase())
+    }
+
+    fn window_property(&self, property: &WindowProperty) -> Option<&str> {
+        self.window_properties
+            .as_ref()
+            .and_then(|props| props.get(property))
+            .map(|value| value.as_str())
+    }
+
+    /// The x coordinate of `rect`.
+    pub fn rect_x(&self) -> i32 {
+        self.rect.0
+    }
+
+    /// The y coordinate of `rect`.
+    pub fn rect_y(&self) -> i32 {
+        self.rect.1
+    }
+
+    /// The width of `rect`.
+    pub fn rect_width(&self) -> i32 {
+        self.rect.2
+    }
+
+    /// The height of `rect`.
+    pub fn rect_height(&self) -> i32 {
+        self.rect.3
+    }
+
+    /// The currently focused container, searching this node and everything below it.
+    pub fn focused(&self) -> Option<&Node> {
+        if self.focused {
+            return Some(self);
+        }
+        self.nodes
+            .iter()
+            .chain(self.floating_nodes.iter())
+            .filter_map(|child| child.focused())
+            .next()
+    }
+
+    /// Whether this is one of i3's internal outputs or workspaces, such as the `__i3` output
+    /// and the `__i3_scratch` workspace that holds the scratchpad.
+    pub fn is_internal(&self) -> bool {
+        self.name
+            .as_ref()
+            .is_some_and(|name| is_internal_name(name))
+    }
+
+    /// The containers holding an actual window, searching this node and everything below it.
+    ///
+    /// These are the nodes without children that have a `window` (or, under Sway, an
+    /// `app_id`). Split containers and empty workspaces are left out.
+    pub fn leaves(&self) -> Vec<&Node> {
+        let mut leaves = vec![];
+        self.collect_leaves(&mut leaves);
+        leaves
+    }
+
+    fn collect_leaves<'a>(&'a self, out: &mut Vec<&'a Node>) {
+        if self.nodes.is_empty() && self.floating_nodes.is_empty() && self.has_client() {
+            out.push(self);
+        }
+        for child in self.nodes.iter().chain(self.floating_nodes.iter()) {
+            child.collect_leaves(out);
+        }
+    }
+
+    /// The floating windows below this node: the leaves (see `leaves`) inside its
+    /// `floating_nodes`, and inside those of every container below it.
+    pub fn floating_windows(&self) -> Vec<&Node> {
+        let mut windows = vec![];
+        self.collect_floating_windows(&mut windows);
+        windows
+    }
+
+    fn collect_floating_windows<'a>(&'a self, out: &mut Vec<&'a Node>) {
+        for floating in &self.floating_nodes {
+            floating.collect_leaves(out);
+        }
+        for child in &self.nodes {
+            child.collect_floating_windows(out);
+        }
+    }
+
+    /// The workspace containing the container with the given id, searching this node and
+    /// everything below it. A workspace is considered to contain itself.
+    pub fn workspace_of(&self, id: i64) -> Option<&Node> {
+        self.workspace_of_below(id, None)
+    }
+
+    fn workspace_of_below<'a>(&'a self, id: i64, workspace: Option<&'a Node>) -> Option<&'a Node> {
+        let workspace = if self.nodetype == NodeType::Workspace {
+            Some(self)
+        } else {
+            workspace
+        };
+        if self.id == id {
+            return workspace;
+        }
+        self.nodes
+            .iter()
+            .chain(self.floating_nodes.iter())
+            .filter_map(|child| child.workspace_of_below(id, workspace))
+            .next()
+    }
+
+    /// Whether any container below this one has the urgency hint set.
+    pub fn has_urgent_descendant(&self) -> bool {
+        self.nodes
+            .iter()
+            .chain(self.floating_nodes.iter())
+            .any(|child| child.urgent || child.has_urgent_descendant())
+    }
+
+    /// The windows with the urgency hint set, searching this node and everything below it.
+    ///
+    /// Unlike `urgent`, which i3 also sets on every parent of an urgent window, this only
+    /// returns the windows themselves (see `leaves`).
+    pub fn urgent_windows(&self) -> Vec<&Node> {
+        self.leaves()
+            .into_iter()
+            .filter(|node| node.urgent)
+            .collect()
+    }
+
+    #[cfg(not(feature = "sway-1-1"))]
+    fn has_client(&self) -> bool {
+        self.window.is_some()
+    }
+
+    #[cfg(feature = "sway-1-1")]
+    fn has_client(&self) -> bool {
+        self.window.is_some() || self.app_id.is_some()
+    }
+
+    /// The ids of the containers from this node down to the focused container, found by
+    /// following the first entry of each container's `focus` list.
+    ///
+    /// Starts with this node's own id. If the chain ends before reaching a focused container
+    /// the path so far is returned.
+    pub fn focus_path(&self) -> Vec<i64> {
+        let mut path = vec![self.id];
+        let mut current = self;
+        while !current.focused {
+            let next = current.focus.first().and_then(|&id| {
+                current
+                    .nodes
+                    .iter()
+                    .chain(current.floating_nodes.iter())
+                    .find(|child| child.id == id)
+            });
+            match next {
+                Some(child) => {
+                    path.push(child.id);
+                    current = child;
+                }
+                None => break,
+            }
+        }
+        path
+    }
+
+    /// The children of this container, tiling and floating, most recently focused first as
+    /// given by `focus`. Children missing from `focus` follow in tree order.
+    pub fn children_in_focus_order(&self) -> Vec<&Node> {
+        let children: Vec<&Node> = self
+            .nodes
+            .iter()
+            .chain(self.floating_nodes.iter())
+            .collect();
+        let mut ordered: Vec<&Node> = self
+            .focus
+            .iter()
+            .filter_map(|&id| children.iter().find(|child| child.id == id).cloned())
+            .collect();
+        for child in children {
+            if !self.focus.contains(&child.id) {
+                ordered.push(child);
+            }
+        }
+        ordered
+    }
+}
+
+/// A difference between two snapshots of the tree, as found by `diff`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeChange {
+    /// A container that only exists in the new tree.
+    Added { id: i64 },
+    /// A container that only exists in the old tree.
+    Removed { id: i64 },
+    /// A container that has a different parent in the new tree.
+    Moved {
+        id: i64,
+        old_parent: Option<i64>,
+        new_parent: Option<i64>,
+    },
+    /// A container whose name changed.
+    Retitled {
+        id: i64,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+/// Collects every node below and including `node` along with the id of its parent.
+fn with_parents<'a>(node: &'a Node, parent: Option<i64>, out: &mut Vec<(&'a Node, Option<i64>)>) {
+    out.push((node, parent));
+    for child in node.nodes.iter().chain(node.floating_nodes.iter()) {
+        with_parents(child, Some(node.id), out);
+    }
+}
+
+/// Compares two snapshots of the tree (as returned by `get_tree`), identifying containers by
+/// their `id`.
+///
+/// Changes for containers in the new tree come first, in depth-first order, followed by the
+/// containers that were removed.
+pub fn diff(old: &Node, new: &Node) -> Vec<TreeChange> {
+    let mut old_nodes = Vec::new();
+    with_parents(old, None, &mut old_nodes);
+    let mut new_nodes = Vec::new();
+    with_parents(new, None, &mut new_nodes);
+    let old_by_id: HashMap<i64, (&Node, Option<i64>)> = old_nodes
+        .iter()
+        .map(|&(node, parent)| (node.id, (node, parent)))
+        .collect();
+    let new_by_id: HashMap<i64, (&Node, Option<i64>)> = new_nodes
+        .iter()
+        .map(|&(node, parent)| (node.id, (node, parent)))
+        .collect();
+
+    let mut changes = Vec::new();
+    for &(node, new_parent) in &new_nodes {
+        match old_by_id.get(&node.id) {
+            None => changes.push(TreeChange::Added { id: node.id }),
+            Some(&(old_node, old_parent)) => {
+                if old_parent != new_parent {
+                    changes.push(TreeChange::Moved {
+                        id: node.id,
+                        old_parent,
+                        new_parent,
+                    });
+                }
+                if old_node.name != node.name {
+                    changes.push(TreeChange::Retitled {
+                        id: node.id,
+                        old: old_node.name.clone(),
+                        new: node.name.clone(),
+                    });
+                }
+            }
+        }
+    }
+    for &(node, _) in &old_nodes {
+        if !new_by_id.contains_key(&node.id) {
+            changes.push(TreeChange::Removed { id: node.id });
+        }
+    }
+    changes
+}
+
+/// A lookup table from container id to container, for when a tree is searched by id many times.
+#[derive(Debug, Clone)]
+pub struct NodeIndex<'a> {
+    nodes: HashMap<i64, &'a Node>,
+}
+
+impl<'a> NodeIndex<'a> {
+    /// Indexes `tree` and everything below it, including floating containers.
+    pub fn from_tree(tree: &'a Node) -> NodeIndex<'a> {
+        let mut nodes = Vec::new();
+        with_parents(tree, None, &mut nodes);
+        NodeIndex {
+            nodes: nodes.into_iter().map(|(node, _)| (node.id, node)).collect(),
+        }
+    }
+
+    /// The container with the given id, if it's part of the indexed tree.
+    pub fn get(&self, id: i64) -> Option<&'a Node> {
+        self.nodes.get(&id).cloned()
+    }
+
+    /// The number of containers in the index.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Whether the index has no containers.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
 }
 
 /// The reply to the `get_marks` request.
@@ -263,7 +840,7 @@ pub struct BarIds {
     pub ids: Vec<String>,
 }
 
-#[derive(Hash, Eq, PartialEq, Debug)]
+#[derive(Hash, Eq, PartialEq, Debug, Clone)]
 pub enum ColorableBarPart {
     /// Background color of the bar.
     Background,
@@ -350,11 +927,70 @@ pub enum ColorableBarPart {
     Unknown,
 }
 
+/// A modifier key, as used in bindings and the bar's `modifier` setting.
+#[derive(Eq, PartialEq, Debug, Hash, Clone, Copy)]
+pub enum ModKey {
+    Shift,
+    Control,
+    Mod1,
+    Mod2,
+    Mod3,
+    Mod4,
+    Mod5,
+    Super,
+}
+
+/// A color parsed from one of the hex codes i3 uses in its bar configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    /// The alpha channel, only present when the color was given as \#rrggbbaa.
+    pub a: Option<u8>,
+}
+
+impl Color {
+    /// Parses a color formatted as \#rgb, \#rrggbb or \#rrggbbaa. Returns `None` if the string
+    /// is not in one of those formats.
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        let digits = hex.strip_prefix('#')?;
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let byte = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+        match digits.len() {
+            3 => {
+                let nibble = |i: usize| u8::from_str_radix(&digits[i..=i], 16).ok().map(|n| n * 17);
+                Some(Color {
+                    r: nibble(0)?,
+                    g: nibble(1)?,
+                    b: nibble(2)?,
+                    a: None,
+                })
+            }
+            6 => Some(Color {
+                r: byte(0)?,
+                g: byte(2)?,
+                b: byte(4)?,
+                a: None,
+            }),
+            8 => Some(Color {
+                r: byte(0)?,
+                g: byte(2)?,
+                b: byte(4)?,
+                a: Some(byte(6)?),
+            }),
+            _ => None,
+        }
+    }
+}
+
 /// The reply to the `get_bar_config` request.
 ///
 /// This can be used by third-party workspace bars (especially i3bar, but others are free to
 /// implement compatible alternatives) to get the bar block configuration from i3.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BarConfig {
     /// The ID for this bar. Included in case you request multiple configurations and want to
     /// differentiate the different replies.
@@ -383,9 +1019,25 @@ pub struct BarConfig {
     /// Should the bar enable verbose output for debugging? Defaults to false.
     pub verbose: bool,
 
+    /// The modifier keys used to show the bar when its mode is hide. Empty if i3 didn't send
+    /// a modifier.
+    pub modifier: Vec<ModKey>,
+
     /// Contains key/value pairs of colors. Each value is a color code in hex, formatted
     /// \#rrggbb (like in HTML).
     pub colors: HashMap<ColorableBarPart, String>,
+
+    /// Any other fields i3 sent, such as `separator_symbol`, keyed by name. Lets you read
+    /// fields this library doesn't model yet.
+    pub extra: HashMap<String, json::Value>,
+}
+
+impl BarConfig {
+    /// The color of the given part of the bar, parsed from its hex code. `None` if the part has
+    /// no color configured or the hex code is malformed.
+    pub fn color(&self, part: ColorableBarPart) -> Option<Color> {
+        self.colors.get(&part).and_then(|hex| Color::from_hex(hex))
+    }
 }
 
 /// The reply to the `get_version` request.
@@ -413,6 +1065,13 @@ pub struct Version {
     pub loaded_config_file_name: String,
 }
 
+impl Version {
+    /// The version as `(major, minor, patch)`, which compares the way versions do.
+    pub fn as_tuple(&self) -> (i32, i32, i32) {
+        (self.major, self.minor, self.patch)
+    }
+}
+
 /// The reply to the `get_binding_modes` request.
 #[cfg(feature = "i3-4-13")]
 #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-13")))]
@@ -429,4 +1088,33 @@ pub struct BindingModes {
 pub struct Config {
     /// A string containing the config file as loaded by i3 most recently.
     pub config: String,
+
+    /// The files included by the config, in the order i3 loaded them.
+    #[cfg(feature = "i3-4-20")]
+    #[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-20")))]
+    pub included_configs: Vec<IncludedConfig>,
+}
+
+/// A file included by the i3 config.
+#[cfg(feature = "i3-4-20")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-20")))]
+#[derive(Debug)]
+pub struct IncludedConfig {
+    /// The absolute path to the included file.
+    pub path: String,
+
+    /// The contents of the file as it was read.
+    pub raw_contents: String,
+
+    /// The contents of the file after i3 replaced variables.
+    pub variable_replaced_contents: String,
+}
+
+/// The reply to the `get_binding_state` request.
+#[cfg(feature = "i3-4-19")]
+#[cfg_attr(feature = "dox", doc(cfg(feature = "i3-4-19")))]
+#[derive(Debug)]
+pub struct BindingState {
+    /// The name of the currently active binding mode.
+    pub name: String,
 }
@@ -0,0 +1,119 @@
+//! Click events from i3bar, for status line programs.
+//!
+//! When a status line program enables `click_events` in its protocol header, i3bar writes an
+//! endless JSON array of click events to the program's stdin, one event per line. See the
+//! [i3bar protocol](https://i3wm.org/docs/i3bar-protocol.html) for details.
+
+use common;
+use reply;
+use serde::de::Error as DeError;
+use serde_json as json;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+
+/// A click on one of the blocks of the status line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClickEvent {
+    /// The name of the block, if it has one.
+    pub name: Option<String>,
+    /// The instance of the block, if it has one.
+    pub instance: Option<String>,
+    /// The X11 button number: 1 for left, 2 for middle, 3 for right and 4 and 5 for scrolling.
+    pub button: u32,
+    /// The x coordinate of the click, relative to the top left of the root window.
+    pub x: i32,
+    /// The y coordinate of the click, relative to the top left of the root window.
+    pub y: i32,
+    /// The x coordinate of the click, relative to the top left of the block.
+    pub relative_x: i32,
+    /// The y coordinate of the click, relative to the top left of the block.
+    pub relative_y: i32,
+    /// The width of the block in pixels.
+    pub width: i32,
+    /// The height of the block in pixels.
+    pub height: i32,
+    /// The modifier keys that were held down during the click.
+    pub modifiers: Vec<reply::ModKey>,
+}
+
+/// An error reading a click event.
+#[derive(Debug)]
+pub enum ClickEventError {
+    /// Error reading from the input.
+    Read(io::Error),
+    /// Read a line but couldn't parse it as a click event.
+    JsonCouldntParse(json::Error),
+}
+
+impl Error for ClickEventError {
+    fn description(&self) -> &str {
+        match *self {
+            ClickEventError::Read(_) => "Error while reading click events",
+            ClickEventError::JsonCouldntParse(_) => "Couldn't parse a click event",
+        }
+    }
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            ClickEventError::Read(ref e) => Some(e),
+            ClickEventError::JsonCouldntParse(ref e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for ClickEventError {
+    #[allow(deprecated)]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+impl ClickEvent {
+    /// Parses a single click event, as found on one line of i3bar's output. The `,` that
+    /// separates events in the array is skipped if present.
+    pub fn from_line(line: &str) -> Result<ClickEvent, json::Error> {
+        let val: json::Value = json::from_str(line.trim().trim_start_matches(',').trim())?;
+        let string = |key| val.get(key).and_then(|v| v.as_str()).map(|s| s.to_owned());
+        let int = |key| match val.get(key) {
+            Some(v) => v
+                .as_i64()
+                .ok_or_else(|| json::Error::custom(format!("`{}` isn't an integer", key))),
+            None => Err(json::Error::missing_field(key)),
+        };
+        Ok(ClickEvent {
+            name: string("name"),
+            instance: string("instance"),
+            button: int("button")? as u32,
+            x: int("x")? as i32,
+            y: int("y")? as i32,
+            relative_x: int("relative_x")? as i32,
+            relative_y: int("relative_y")? as i32,
+            width: int("width")? as i32,
+            height: int("height")? as i32,
+            modifiers: match val.get("modifiers").and_then(|m| m.as_array()) {
+                Some(modifiers) => {
+                    common::build_modifiers(modifiers.iter().filter_map(|m| m.as_str()))
+                }
+                None => vec![],
+            },
+        })
+    }
+}
+
+/// Reads click events as i3bar writes them, typically from stdin. The opening `[` of the array
+/// and blank lines are skipped. Iteration ends when the input does.
+pub fn read_click_events<R: Read>(
+    r: R,
+) -> impl Iterator<Item = Result<ClickEvent, ClickEventError>> {
+    BufReader::new(r).lines().filter_map(|line| match line {
+        Ok(line) => {
+            let line = line.trim().trim_start_matches('[');
+            if line.trim().is_empty() {
+                None
+            } else {
+                Some(ClickEvent::from_line(line).map_err(ClickEventError::JsonCouldntParse))
+            }
+        }
+        Err(e) => Some(Err(ClickEventError::Read(e))),
+    })
+}